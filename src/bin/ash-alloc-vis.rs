@@ -0,0 +1,727 @@
+#![deny(clippy::as_conversions)]
+#![deny(clippy::panic)]
+#![deny(clippy::unwrap_used)]
+
+//! Renders the memory map of a state dump written by `Allocator::dump_json` as SVG or as a self
+//! contained HTML file. Dumps of VMA's `vmaBuildStatsString` are supported too.
+//!
+//! ## Usage:
+//! ```text
+//! ash-alloc-vis <dump.json> <output.svg|output.html>
+//! ```
+//!
+//! HTML is written if the output path ends with `.html` or `.htm`, SVG otherwise.
+//!
+//! Every memory block is drawn as one horizontal bar. The chunks are colored by their type and
+//! the swatch in front of a bar is colored by the lifetime of the pool.
+
+use std::fmt::Write;
+use std::process::ExitCode;
+
+/// The width of the labels in front of the bars.
+const LABEL_WIDTH: f64 = 380.0;
+/// The width of a bar of the biggest block.
+const BAR_WIDTH: f64 = 800.0;
+const ROW_HEIGHT: f64 = 20.0;
+const ROW_GAP: f64 = 6.0;
+const MARGIN: f64 = 10.0;
+const LEGEND_HEIGHT: f64 = 50.0;
+
+/// The colors of the chunk types.
+const FREE_COLOR: &str = "#d0d0d0";
+const LINEAR_COLOR: &str = "#4e79a7";
+const OPTIMAL_COLOR: &str = "#59a14f";
+const UNKNOWN_COLOR: &str = "#f28e2b";
+
+/// The colors of the lifetimes, used in the order the lifetimes appear in the dump.
+const LIFETIME_COLORS: [&str; 8] = [
+    "#e15759", "#76b7b2", "#edc948", "#b07aa1", "#ff9da7", "#9c755f", "#bab0ac", "#17becf",
+];
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let [_, input, output] = args.as_slice() else {
+        eprintln!("usage: ash-alloc-vis <dump.json> <output.svg|output.html>");
+        return ExitCode::FAILURE;
+    };
+
+    match run(input, output) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("ash-alloc-vis: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(input: &str, output: &str) -> Result<(), String> {
+    let dump = std::fs::read_to_string(input)
+        .map_err(|err| format!("can't read \"{}\": {}", input, err))?;
+    let dump = Parser::new(&dump).parse()?;
+    let blocks = collect_blocks(&dump);
+
+    let svg = render_svg(&blocks);
+    let document = if output.ends_with(".html") || output.ends_with(".htm") {
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n{}</body>\n</html>\n",
+            escape_xml(input),
+            svg
+        )
+    } else {
+        svg
+    };
+
+    std::fs::write(output, document).map_err(|err| format!("can't write \"{}\": {}", output, err))
+}
+
+/// A memory block of the dump.
+#[derive(Debug)]
+struct Block {
+    memory_type: String,
+    lifetime: String,
+    name: String,
+    size: f64,
+    chunks: Vec<Chunk>,
+}
+
+/// A chunk inside a memory block.
+#[derive(Debug)]
+struct Chunk {
+    offset: f64,
+    size: f64,
+    kind: String,
+    name: Option<String>,
+}
+
+/// Collects the blocks of the default and custom pools.
+fn collect_blocks(dump: &Value) -> Vec<Block> {
+    let mut blocks = Vec::new();
+
+    for section in ["DefaultPools", "CustomPools"] {
+        let Some(Value::Object(memory_types)) = dump.get(section) else {
+            continue;
+        };
+
+        for (memory_type, pools) in memory_types {
+            // Default pools are written as a single object, custom pools as an array.
+            let pools = match pools {
+                Value::Array(pools) => pools.iter().collect(),
+                pool => vec![pool],
+            };
+
+            for pool in pools {
+                let lifetime = pool
+                    .get("Name")
+                    .and_then(Value::as_str)
+                    .unwrap_or("Default")
+                    .to_owned();
+
+                if let Some(Value::Object(pool_blocks)) = pool.get("Blocks") {
+                    for (id, block) in pool_blocks {
+                        let chunks = match block.get("Suballocations") {
+                            Some(Value::Array(chunks)) => chunks
+                                .iter()
+                                .map(|chunk| Chunk {
+                                    offset: chunk.number("Offset"),
+                                    size: chunk.number("Size"),
+                                    kind: chunk
+                                        .get("Type")
+                                        .and_then(Value::as_str)
+                                        .unwrap_or("UNKNOWN")
+                                        .to_owned(),
+                                    name: chunk
+                                        .get("Name")
+                                        .and_then(Value::as_str)
+                                        .map(str::to_owned),
+                                })
+                                .collect(),
+                            _ => Vec::new(),
+                        };
+
+                        blocks.push(Block {
+                            memory_type: memory_type.clone(),
+                            lifetime: lifetime.clone(),
+                            name: format!("block {}", id),
+                            size: block.number("TotalBytes"),
+                            chunks,
+                        });
+                    }
+                }
+
+                if let Some(Value::Array(dedicated)) = pool.get("DedicatedAllocations") {
+                    for allocation in dedicated {
+                        let size = allocation.number("Size");
+                        blocks.push(Block {
+                            memory_type: memory_type.clone(),
+                            lifetime: lifetime.clone(),
+                            name: "dedicated".to_owned(),
+                            size,
+                            chunks: vec![Chunk {
+                                offset: 0.0,
+                                size,
+                                kind: allocation
+                                    .get("Type")
+                                    .and_then(Value::as_str)
+                                    .unwrap_or("UNKNOWN")
+                                    .to_owned(),
+                                name: allocation
+                                    .get("Name")
+                                    .and_then(Value::as_str)
+                                    .map(str::to_owned),
+                            }],
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    blocks
+}
+
+/// Renders one bar per block, scaled to the size of the biggest block.
+fn render_svg(blocks: &[Block]) -> String {
+    let max_size = blocks.iter().map(|block| block.size).fold(1.0, f64::max);
+
+    let mut lifetimes: Vec<&str> = Vec::new();
+    for block in blocks {
+        if !lifetimes.contains(&block.lifetime.as_str()) {
+            lifetimes.push(&block.lifetime);
+        }
+    }
+    let lifetime_color = |lifetime: &str| {
+        let index = lifetimes
+            .iter()
+            .position(|known| *known == lifetime)
+            .unwrap_or(0);
+        LIFETIME_COLORS[index % LIFETIME_COLORS.len()]
+    };
+
+    let width = 2.0 * MARGIN + LABEL_WIDTH + BAR_WIDTH;
+    let height = 2.0 * MARGIN + LEGEND_HEIGHT + count(blocks.len()) * (ROW_HEIGHT + ROW_GAP);
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" font-family=\"monospace\" font-size=\"12\">",
+        width, height
+    );
+    let _ = writeln!(
+        svg,
+        "<rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>"
+    );
+
+    // The legend of the chunk types and lifetimes.
+    let mut x = MARGIN;
+    let mut y = MARGIN;
+    for (label, color) in [
+        ("free", FREE_COLOR),
+        ("linear", LINEAR_COLOR),
+        ("optimal", OPTIMAL_COLOR),
+        ("unknown", UNKNOWN_COLOR),
+    ] {
+        x = legend_entry(&mut svg, x, y, label, color);
+    }
+    x = MARGIN;
+    y += ROW_HEIGHT;
+    for lifetime in lifetimes.iter() {
+        x = legend_entry(&mut svg, x, y, lifetime, lifetime_color(lifetime));
+    }
+
+    y = MARGIN + LEGEND_HEIGHT;
+    for block in blocks {
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"/>",
+            MARGIN,
+            y,
+            ROW_HEIGHT / 2.0,
+            ROW_HEIGHT,
+            lifetime_color(&block.lifetime)
+        );
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\">{}</text>",
+            MARGIN + ROW_HEIGHT,
+            y + ROW_HEIGHT * 0.7,
+            escape_xml(&format!(
+                "{} {} {} ({})",
+                block.lifetime,
+                block.memory_type,
+                block.name,
+                format_bytes(block.size)
+            ))
+        );
+
+        let scale = BAR_WIDTH / max_size;
+        let bar_x = MARGIN + LABEL_WIDTH;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\" stroke=\"#808080\"/>",
+            bar_x,
+            y,
+            block.size * scale,
+            ROW_HEIGHT,
+            FREE_COLOR
+        );
+        for chunk in block.chunks.iter() {
+            let color = match chunk.kind.as_str() {
+                "FREE" => continue,
+                "BUFFER" | "IMAGE_LINEAR" => LINEAR_COLOR,
+                "IMAGE_OPTIMAL" => OPTIMAL_COLOR,
+                _ => UNKNOWN_COLOR,
+            };
+            let tooltip = match &chunk.name {
+                Some(name) => format!(
+                    "{}: {} at {} ({})",
+                    name,
+                    format_bytes(chunk.size),
+                    chunk.offset,
+                    chunk.kind
+                ),
+                None => format!(
+                    "{} at {} ({})",
+                    format_bytes(chunk.size),
+                    chunk.offset,
+                    chunk.kind
+                ),
+            };
+            let _ = writeln!(
+                svg,
+                "<rect x=\"{:.2}\" y=\"{:.1}\" width=\"{:.2}\" height=\"{:.1}\" fill=\"{}\"><title>{}</title></rect>",
+                bar_x + chunk.offset * scale,
+                y,
+                // Keep tiny chunks visible.
+                (chunk.size * scale).max(0.5),
+                ROW_HEIGHT,
+                color,
+                escape_xml(&tooltip)
+            );
+        }
+
+        y += ROW_HEIGHT + ROW_GAP;
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Writes a colored swatch with a label and returns the x position of the next entry.
+fn legend_entry(svg: &mut String, x: f64, y: f64, label: &str, color: &str) -> f64 {
+    let _ = writeln!(
+        svg,
+        "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"12\" height=\"12\" fill=\"{}\"/><text x=\"{:.1}\" y=\"{:.1}\">{}</text>",
+        x,
+        y,
+        color,
+        x + 16.0,
+        y + 10.0,
+        escape_xml(label)
+    );
+    x + 32.0 + count(label.chars().count()) * 7.5
+}
+
+fn count(count: usize) -> f64 {
+    f64::from(u32::try_from(count).unwrap_or(u32::MAX))
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", value, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Escapes the markup characters and drops the characters XML 1.0 doesn't allow.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars().filter(|&c| is_xml_char(c)) {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Returns true if the character is allowed in XML 1.0 documents.
+fn is_xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r' | '\u{20}'..='\u{D7FF}' | '\u{E000}'..='\u{FFFD}')
+        || c >= '\u{10000}'
+}
+
+/// A parsed JSON value. Objects keep the order of their members.
+#[derive(Debug)]
+enum Value {
+    /// `true`, `false` or `null`. Their values are not needed to render a dump.
+    Literal,
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The number of a member, or zero if the member is missing.
+    fn number(&self, key: &str) -> f64 {
+        match self.get(key) {
+            Some(Value::Number(value)) => *value,
+            _ => 0.0,
+        }
+    }
+}
+
+/// A minimal recursive descent JSON parser.
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, position: 0 }
+    }
+
+    fn parse(mut self) -> Result<Value, String> {
+        let value = self.value()?;
+        self.skip_whitespace();
+        if self.position != self.input.len() {
+            return Err(self.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('t') => self.literal("true"),
+            Some('f') => self.literal("false"),
+            Some('n') => self.literal("null"),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            _ => Err(self.error("expected a value")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(Value::Object(members));
+        }
+
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            let value = self.value()?;
+            members.push((key, value));
+
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => return Ok(Value::Object(members)),
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(Value::Array(elements));
+        }
+
+        loop {
+            elements.push(self.value()?);
+
+            self.skip_whitespace();
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => return Ok(Value::Array(elements)),
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => match self.next() {
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('/') => string.push('/'),
+                    Some('b') => string.push('\u{8}'),
+                    Some('f') => string.push('\u{c}'),
+                    Some('n') => string.push('\n'),
+                    Some('r') => string.push('\r'),
+                    Some('t') => string.push('\t'),
+                    Some('u') => {
+                        let digits = self
+                            .input
+                            .get(self.position..self.position + 4)
+                            .ok_or_else(|| self.error("incomplete unicode escape"))?;
+                        let code = u32::from_str_radix(digits, 16)
+                            .map_err(|_| self.error("invalid unicode escape"))?;
+                        self.position += 4;
+                        // Surrogate pairs are not combined, they don't appear in dumps.
+                        string.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    }
+                    _ => return Err(self.error("invalid escape")),
+                },
+                Some(c) => string.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.position;
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+        self.input[start..self.position]
+            .parse()
+            .map(Value::Number)
+            .map_err(|_| self.error("invalid number"))
+    }
+
+    fn literal(&mut self, literal: &str) -> Result<Value, String> {
+        if self.input[self.position..].starts_with(literal) {
+            self.position += literal.len();
+            Ok(Value::Literal)
+        } else {
+            Err(self.error("invalid literal"))
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.next() == Some(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("invalid dump at byte {}: {}", self.position, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A dump as written by `Allocator::dump_json`, with a debug margin in front of the allocation.
+    const DUMP: &str = r#"{"General":{"API":"Vulkan","bufferImageGranularity":1024,"memoryHeapCount":1,"memoryTypeCount":2},"Total":{"BlockCount":2,"BlockBytes":1052672,"AllocationCount":2,"AllocationBytes":5120},"MemoryInfo":{},"DefaultPools":{},"CustomPools":{"Type 1":[{"Name":"Static","PreferredBlockSize":1048576,"Blocks":{"1":{"MapRefs":0,"TotalBytes":1048576,"UnusedBytes":1047552,"Allocations":1,"UnusedRanges":2,"Suballocations":[{"Offset":0,"Type":"FREE","Size":256},{"Offset":256,"Type":"BUFFER","Size":1024,"CustomData":"0xab","Name":"texture \"a\"\u0001"},{"Offset":1280,"Type":"FREE","Size":1047296}]}},"DedicatedAllocations":[{"Type":"UNKNOWN","Size":4096,"Name":"shadow map"}]}]}}"#;
+
+    /// A dump as written by VMA's `vmaBuildStatsString` with a detailed map.
+    const VMA_DUMP: &str = r#"{
+        "General": {"API": "Vulkan", "apiVersion": "1.3.0", "GPU": "Test GPU"},
+        "Total": {"BlockCount": 1, "BlockBytes": 268435456, "AllocationCount": 2},
+        "DefaultPools": {
+            "Type 0": {
+                "PreferredBlockSize": 268435456,
+                "Blocks": {
+                    "0": {
+                        "MapRefs": 0,
+                        "TotalBytes": 268435456,
+                        "UnusedBytes": 268369920,
+                        "Allocations": 1,
+                        "UnusedRanges": 1,
+                        "Suballocations": [
+                            {"Offset": 0, "Type": "IMAGE_OPTIMAL", "Size": 65536, "Usage": 6},
+                            {"Offset": 65536, "Type": "FREE", "Size": 268369920}
+                        ]
+                    }
+                },
+                "DedicatedAllocations": [
+                    {"Type": "BUFFER", "Size": 1048576, "Usage": 130, "CustomData": null}
+                ]
+            }
+        },
+        "CustomPools": {}
+    }"#;
+
+    #[test]
+    fn dump_round_trip() {
+        let dump = Parser::new(DUMP).parse().expect("valid dump");
+        let blocks = collect_blocks(&dump);
+        assert_eq!(blocks.len(), 2);
+
+        let block = &blocks[0];
+        assert_eq!(block.memory_type, "Type 1");
+        assert_eq!(block.lifetime, "Static");
+        assert_eq!(block.name, "block 1");
+        assert_eq!(block.size, 1048576.0);
+        assert_eq!(block.chunks.len(), 3);
+        assert_eq!(block.chunks[0].kind, "FREE");
+        assert_eq!(block.chunks[1].offset, 256.0);
+        assert_eq!(block.chunks[1].size, 1024.0);
+        assert_eq!(block.chunks[1].kind, "BUFFER");
+        assert_eq!(block.chunks[1].name.as_deref(), Some("texture \"a\"\u{1}"));
+        assert_eq!(block.chunks[2].offset, 1280.0);
+
+        let dedicated = &blocks[1];
+        assert_eq!(dedicated.name, "dedicated");
+        assert_eq!(dedicated.size, 4096.0);
+        assert_eq!(dedicated.chunks[0].kind, "UNKNOWN");
+        assert_eq!(dedicated.chunks[0].name.as_deref(), Some("shadow map"));
+    }
+
+    #[test]
+    fn vma_dump() {
+        let dump = Parser::new(VMA_DUMP).parse().expect("valid dump");
+        let blocks = collect_blocks(&dump);
+        assert_eq!(blocks.len(), 2);
+
+        assert_eq!(blocks[0].memory_type, "Type 0");
+        assert_eq!(blocks[0].lifetime, "Default");
+        assert_eq!(blocks[0].size, 268435456.0);
+        assert_eq!(blocks[0].chunks.len(), 2);
+        assert_eq!(blocks[0].chunks[0].kind, "IMAGE_OPTIMAL");
+        assert_eq!(blocks[0].chunks[0].name, None);
+
+        assert_eq!(blocks[1].name, "dedicated");
+        assert_eq!(blocks[1].chunks[0].kind, "BUFFER");
+        assert_eq!(blocks[1].chunks[0].size, 1048576.0);
+    }
+
+    /// Checks that every element is closed in the right order and that no markup characters
+    /// appear in text.
+    fn assert_well_formed(document: &str) {
+        let mut open = Vec::new();
+        let mut rest = document;
+        while let Some(start) = rest.find('<') {
+            assert!(!rest[..start].contains('>'), "unescaped '>' in text");
+            let end = rest[start..].find('>').expect("unclosed tag") + start;
+            let tag = &rest[start + 1..end];
+            assert!(!tag.contains('<'), "unescaped '<' in tag {:?}", tag);
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name), "mismatched closing tag");
+            } else if !tag.ends_with('/') && !tag.starts_with('!') {
+                open.push(tag.split_whitespace().next().expect("empty tag"));
+            }
+            rest = &rest[end + 1..];
+        }
+        assert!(open.is_empty(), "unclosed elements {:?}", open);
+    }
+
+    #[test]
+    fn render_escapes_names() {
+        let dump = Parser::new(DUMP).parse().expect("valid dump");
+        let svg = render_svg(&collect_blocks(&dump));
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains("<title>texture &quot;a&quot;: 1.0 KiB at 256 (BUFFER)</title>"));
+        // Control characters are not allowed in XML 1.0 documents.
+        assert!(!svg.contains('\u{1}'));
+        assert!(svg.chars().all(is_xml_char));
+        assert_well_formed(&svg);
+        // Free chunks are part of the background bar.
+        assert_eq!(svg.matches("<title>").count(), 2);
+    }
+
+    #[test]
+    fn string_escapes() {
+        let value = Parser::new(r#""a\"b\\c\/d\n\t\u0041""#)
+            .parse()
+            .expect("valid string");
+        assert_eq!(value.as_str(), Some("a\"b\\c/d\n\tA"));
+        assert_eq!(
+            escape_xml("<a & \"b\" 'c'>"),
+            "&lt;a &amp; &quot;b&quot; &apos;c&apos;&gt;"
+        );
+        assert_eq!(escape_xml("a\u{1}\tb\u{FFFE}\u{FFFF}c"), "a\tbc");
+    }
+
+    #[test]
+    fn malformed_input() {
+        for input in [
+            "",
+            "{",
+            "{\"a\":}",
+            "{\"a\" 1}",
+            "{\"a\":1,}",
+            "[1,]",
+            "[1 2]",
+            "\"unterminated",
+            "\"\\x\"",
+            "\"\\u12\"",
+            "tru",
+            "-",
+            "{} {}",
+        ] {
+            assert!(
+                Parser::new(input).parse().is_err(),
+                "{:?} was accepted",
+                input
+            );
+        }
+
+        assert_eq!(
+            Parser::new("{\"a\":}").parse().expect_err("missing value"),
+            "invalid dump at byte 5: expected a value"
+        );
+    }
+}
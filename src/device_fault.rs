@@ -0,0 +1,177 @@
+//! Device addresses of allocations and the attribution of device faults to them.
+
+use crate::{
+    get_allocation_pool, Allocation, AllocationInfo, Allocator, AllocatorError, Lifetime,
+    MemoryPool, Result,
+};
+use ash::vk;
+
+/// The allocations near a faulting address reported by `VK_EXT_device_fault`.
+#[derive(Clone, Debug)]
+pub struct DeviceFaultAttribution<LT: Lifetime> {
+    /// The reported address info.
+    pub address_info: vk::DeviceFaultAddressInfoEXT,
+    /// Allocations whose device address range overlaps the range the address was reported for.
+    pub overlapping: Vec<AllocationInfo<LT>>,
+    /// The closest allocation that ends before the reported range.
+    pub preceding: Option<AllocationInfo<LT>>,
+    /// The closest allocation that starts after the reported range.
+    pub following: Option<AllocationInfo<LT>>,
+}
+
+impl<LT: Lifetime> Allocator<LT> {
+    /// Registers the device address of the resource bound to the allocation, so faults at this
+    /// address can be attributed to the allocation. The address is forgotten once the allocation
+    /// is freed.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn register_device_address(
+        &self,
+        allocation: &Allocation<LT>,
+        device_address: vk::DeviceAddress,
+    ) -> Result<()> {
+        let pools = self.pools.read();
+        let mut pool = get_allocation_pool(&pools, allocation)?.lock();
+        pool.set_device_address(allocation, device_address)
+    }
+
+    /// Binds the memory of the allocation to a buffer and registers the device address of the
+    /// buffer. The buffer needs to be created with the `SHADER_DEVICE_ADDRESS` usage.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided device, buffer and allocation are in a valid state.
+    #[cfg(feature = "vk-buffer-device-address")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn bind_buffer_memory_with_device_address(
+        &self,
+        device: &ash::Device,
+        buffer: vk::Buffer,
+        allocation: &Allocation<LT>,
+    ) -> Result<vk::DeviceAddress> {
+        self.bind_buffer_memory(device, buffer, allocation, None)?;
+
+        let info = vk::BufferDeviceAddressInfo::default().buffer(buffer);
+        let device_address = device.get_buffer_device_address(&info);
+        self.register_device_address(allocation, device_address)?;
+
+        Ok(device_address)
+    }
+
+    /// Matches the faulting addresses reported by `vkGetDeviceFaultInfoEXT` against the
+    /// allocations with a registered device address.
+    ///
+    /// The allocator doesn't know the device addresses of the resources bound to its memory.
+    /// Only allocations registered with `register_device_address` (or bound with
+    /// `bind_buffer_memory_with_device_address` when the `vk-buffer-device-address` feature is
+    /// enabled) are considered. Faults in unregistered allocations are never attributed.
+    ///
+    /// The reported address is only precise up to its `address_precision`, so every allocation
+    /// overlapping that range is returned, together with the closest allocations around it.
+    /// Instruction pointer addresses point into shader code and are returned without any
+    /// allocations.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn attribute_device_faults(
+        &self,
+        address_infos: &[vk::DeviceFaultAddressInfoEXT],
+    ) -> Vec<DeviceFaultAttribution<LT>> {
+        let mut allocations = Vec::new();
+        self.for_each_allocation(|info| {
+            if info.device_address.is_some() {
+                allocations.push(info.clone());
+            }
+        });
+
+        address_infos
+            .iter()
+            .map(|address_info| attribute_device_fault(*address_info, &allocations))
+            .collect()
+    }
+}
+
+impl<LT: Lifetime> MemoryPool<LT> {
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn set_device_address(
+        &mut self,
+        allocation: &Allocation<LT>,
+        device_address: vk::DeviceAddress,
+    ) -> Result<()> {
+        let label = if let Some(chunk_key) = allocation.chunk_key {
+            self.check_chunk_key(chunk_key)?;
+            &mut self.chunks[chunk_key.index.get()]
+                .as_mut()
+                .ok_or(AllocatorError::CantFindChunk)?
+                .label
+        } else {
+            self.check_dedicated_block_key(allocation.block_key)?;
+            &mut self.blocks[allocation.block_key.index.get()]
+                .as_mut()
+                .ok_or(AllocatorError::CantFindBlock)?
+                .label
+        };
+        label.device_address = Some(device_address);
+
+        Ok(())
+    }
+}
+
+/// Finds the allocations in and around the range the faulting address was reported for.
+fn attribute_device_fault<LT: Lifetime>(
+    address_info: vk::DeviceFaultAddressInfoEXT,
+    allocations: &[AllocationInfo<LT>],
+) -> DeviceFaultAttribution<LT> {
+    // The precision is a power of two. The fault happened somewhere inside the aligned range.
+    let precision = address_info.address_precision.max(1);
+    let start = address_info.reported_address & !(precision - 1);
+    let end = start.saturating_add(precision);
+
+    let mut attribution = DeviceFaultAttribution {
+        address_info,
+        overlapping: Vec::new(),
+        preceding: None,
+        following: None,
+    };
+
+    // Shader code doesn't live in the allocations, so instruction pointers can't be attributed.
+    if matches!(
+        address_info.address_type,
+        vk::DeviceFaultAddressTypeEXT::INSTRUCTION_POINTER_UNKNOWN
+            | vk::DeviceFaultAddressTypeEXT::INSTRUCTION_POINTER_INVALID
+            | vk::DeviceFaultAddressTypeEXT::INSTRUCTION_POINTER_FAULT
+    ) {
+        return attribution;
+    }
+
+    // Allocations can alias in the address space, so the closest preceding allocation is the one
+    // that ends closest to the reported range.
+    let mut preceding_end = None;
+    let mut following_start = None;
+    for info in allocations {
+        let Some(address) = info.device_address else {
+            continue;
+        };
+        let allocation_end = address.saturating_add(info.size);
+
+        if allocation_end <= start {
+            if preceding_end < Some(allocation_end) {
+                preceding_end = Some(allocation_end);
+                attribution.preceding = Some(info.clone());
+            }
+        } else if address >= end {
+            let is_closer = match following_start {
+                Some(following_start) => address < following_start,
+                None => true,
+            };
+            if is_closer {
+                following_start = Some(address);
+                attribution.following = Some(info.clone());
+            }
+        } else {
+            attribution.overlapping.push(info.clone());
+        }
+    }
+
+    attribution
+        .overlapping
+        .sort_by_key(|info| info.device_address);
+
+    attribution
+}
@@ -11,16 +11,30 @@ pub enum AllocatorError {
     OutOfMemory,
     /// Failed to map the memory.
     FailedToMap,
+    /// Failed to import external memory.
+    FailedToImport,
+    /// Failed to bind the memory to a resource.
+    FailedToBind,
     /// No free slots ara available.
     NotSlotsAvailable,
     /// No compatible memory type was found.
     NoCompatibleMemoryTypeFound,
     /// Alignment is not a power of 2.
     InvalidAlignment,
+    /// The size of the memory is zero.
+    ZeroSize,
     /// Can't find referenced chunk in chunk list.
     CantFindChunk,
     /// Can't find referenced block in block list.
     CantFindBlock,
+    /// The allocation was already freed and its memory may be used by another allocation.
+    StaleAllocation,
+    /// The allocator doesn't own a device. Create it with `Allocator::new_managed`.
+    NotManaged,
+    /// The debug margins around an allocation were overwritten.
+    MemoryCorruption(String),
+    /// The internal state of the allocator is inconsistent.
+    InvalidState(String),
     /// An allocator implementation error.
     Internal(String),
 }
@@ -37,6 +51,12 @@ impl std::fmt::Display for AllocatorError {
             AllocatorError::FailedToMap => {
                 write!(f, "failed to map memory")
             }
+            AllocatorError::FailedToImport => {
+                write!(f, "failed to import external memory")
+            }
+            AllocatorError::FailedToBind => {
+                write!(f, "failed to bind memory")
+            }
             AllocatorError::NotSlotsAvailable => {
                 write!(f, "no free slots available")
             }
@@ -46,6 +66,9 @@ impl std::fmt::Display for AllocatorError {
             AllocatorError::InvalidAlignment => {
                 write!(f, "alignment is not a power of 2")
             }
+            AllocatorError::ZeroSize => {
+                write!(f, "memory size is zero")
+            }
             AllocatorError::Internal(message) => {
                 write!(f, "{}", message)
             }
@@ -55,6 +78,18 @@ impl std::fmt::Display for AllocatorError {
             AllocatorError::CantFindBlock => {
                 write!(f, "can't find block in block list")
             }
+            AllocatorError::StaleAllocation => {
+                write!(f, "allocation was already freed")
+            }
+            AllocatorError::NotManaged => {
+                write!(f, "allocator doesn't own a device")
+            }
+            AllocatorError::MemoryCorruption(message) => {
+                write!(f, "memory corruption: {}", message)
+            }
+            AllocatorError::InvalidState(message) => {
+                write!(f, "invalid allocator state: {}", message)
+            }
         }
     }
 }
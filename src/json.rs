@@ -0,0 +1,438 @@
+//! JSON dumps of the allocator state.
+
+use std::fmt::Display;
+use std::num::NonZeroUsize;
+
+use crate::{AllocationInfo, Allocator, ChunkType, Lifetime, MemoryPool, Result, Statistics};
+use ash::vk;
+
+/// The memory heap flags and their names in the dump.
+const HEAP_FLAGS: [(vk::MemoryHeapFlags, &str); 2] = [
+    (vk::MemoryHeapFlags::DEVICE_LOCAL, "DEVICE_LOCAL"),
+    (vk::MemoryHeapFlags::MULTI_INSTANCE, "MULTI_INSTANCE"),
+];
+
+/// The memory property flags and their names in the dump.
+const PROPERTY_FLAGS: [(vk::MemoryPropertyFlags, &str); 6] = [
+    (vk::MemoryPropertyFlags::DEVICE_LOCAL, "DEVICE_LOCAL"),
+    (vk::MemoryPropertyFlags::HOST_VISIBLE, "HOST_VISIBLE"),
+    (vk::MemoryPropertyFlags::HOST_COHERENT, "HOST_COHERENT"),
+    (vk::MemoryPropertyFlags::HOST_CACHED, "HOST_CACHED"),
+    (
+        vk::MemoryPropertyFlags::LAZILY_ALLOCATED,
+        "LAZILY_ALLOCATED",
+    ),
+    (vk::MemoryPropertyFlags::PROTECTED, "PROTECTED"),
+];
+
+impl<LT: Lifetime> Allocator<LT> {
+    /// Writes the state of the allocator as JSON. Contains every memory heap, memory type,
+    /// lifetime pool, block and chunk.
+    ///
+    /// The layout follows the detailed map of VMA's `vmaBuildStatsString`, so tools like
+    /// `VmaDumpVis` can read it. Lifetime pools are written as `CustomPools` named after their
+    /// lifetime. Linear chunks have the type `BUFFER`, optimal chunks `IMAGE_OPTIMAL`, free chunks
+    /// `FREE` and dedicated allocations `UNKNOWN`. Alignment padding and debug margins are written
+    /// as `FREE` ranges. The tag of an allocation is written as its `CustomData`. With the
+    /// `backtrace` feature, allocations carry the backtrace of their creation as `Backtrace`.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn dump_json(&self) -> Result<String> {
+        let statistics = self.statistics()?;
+        let mut json = JsonWriter::default();
+
+        json.begin_object();
+
+        json.key("General");
+        json.begin_object();
+        json.key("API");
+        json.string("Vulkan");
+        json.key("bufferImageGranularity");
+        json.number(self.buffer_image_granularity);
+        json.key("memoryHeapCount");
+        json.number(self.memory_properties.memory_heap_count);
+        json.key("memoryTypeCount");
+        json.number(self.memory_properties.memory_type_count);
+        json.end_object();
+
+        json.key("Total");
+        write_statistics(&mut json, &statistics.total);
+
+        json.key("MemoryInfo");
+        json.begin_object();
+        for (heap_index, heap_statistics) in statistics.heaps.iter().enumerate() {
+            let heap = &self.memory_properties.memory_heaps[heap_index];
+            json.key(&format!("Heap {}", heap_index));
+            json.begin_object();
+            json.key("Flags");
+            json.begin_array();
+            for (flag, name) in HEAP_FLAGS {
+                if heap.flags.contains(flag) {
+                    json.string(name);
+                }
+            }
+            json.end_array();
+            json.key("Size");
+            json.number(heap.size);
+            json.key("Stats");
+            write_statistics(&mut json, heap_statistics);
+
+            json.key("MemoryPools");
+            json.begin_object();
+            for (type_index, memory_type) in self.memory_types.iter().enumerate() {
+                let heap_index: u32 = heap_index.try_into()?;
+                if memory_type.heap_index != heap_index {
+                    continue;
+                }
+                json.key(&format!("Type {}", type_index));
+                json.begin_object();
+                json.key("Flags");
+                json.begin_array();
+                for (flag, name) in PROPERTY_FLAGS {
+                    if memory_type.property_flags.contains(flag) {
+                        json.string(name);
+                    }
+                }
+                json.end_array();
+                json.key("Stats");
+                write_statistics(&mut json, &statistics.memory_types[type_index]);
+                json.end_object();
+            }
+            json.end_object();
+
+            json.end_object();
+        }
+        json.end_object();
+
+        // All memory is owned by lifetime pools, so there are no default pools.
+        json.key("DefaultPools");
+        json.begin_object();
+        json.end_object();
+
+        json.key("CustomPools");
+        json.begin_object();
+        let pools = self.pools.read();
+        for type_index in 0..self.memory_types.len() {
+            let mut has_pools = false;
+            for lifetime_pools in pools.values() {
+                let Some(pool) = lifetime_pools.get(type_index) else {
+                    continue;
+                };
+                let pool = pool.lock();
+                if pool.blocks.iter().flatten().next().is_none() {
+                    continue;
+                }
+
+                if !has_pools {
+                    json.key(&format!("Type {}", type_index));
+                    json.begin_array();
+                    has_pools = true;
+                }
+                pool.dump_json(&mut json)?;
+            }
+            if has_pools {
+                json.end_array();
+            }
+        }
+        json.end_object();
+
+        json.end_object();
+
+        Ok(json.finish())
+    }
+}
+
+impl<LT: Lifetime> MemoryPool<LT> {
+    /// Writes the pool with its blocks and chunks as a VMA custom pool.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn dump_json(&self, json: &mut JsonWriter) -> Result<()> {
+        json.begin_object();
+        json.key("Name");
+        if self.device_mask == 0 {
+            json.string(&format!("{:?}", self.lifetime));
+        } else {
+            json.string(&format!(
+                "{:?} (device mask {})",
+                self.lifetime, self.device_mask
+            ));
+        }
+        json.key("PreferredBlockSize");
+        json.number(self.block_size);
+
+        json.key("Blocks");
+        json.begin_object();
+        for (index, block) in self.blocks.iter().enumerate() {
+            let (Some(block_key), Some(block)) = (NonZeroUsize::new(index), block) else {
+                continue;
+            };
+            if block.is_dedicated {
+                continue;
+            }
+
+            let mut chunks: Vec<_> = self
+                .chunks
+                .iter()
+                .enumerate()
+                .filter_map(|(index, chunk)| Some((NonZeroUsize::new(index)?, chunk.as_ref()?)))
+                .filter(|(_, chunk)| chunk.block_key == block_key)
+                .collect();
+            chunks.sort_by_key(|(_, chunk)| chunk.offset);
+
+            // Alignment padding and debug margins lie between the allocations and are written as
+            // free ranges, merged with adjacent free chunks.
+            let mut suballocations = Vec::new();
+            let mut end = 0;
+            for (chunk_key, chunk) in chunks {
+                match self.chunk_info(chunk_key) {
+                    Some(info) if chunk.chunk_type != ChunkType::Free => {
+                        if info.offset > end {
+                            suballocations.push(Suballocation::Free {
+                                offset: end,
+                                size: info.offset - end,
+                            });
+                        }
+                        end = info.offset + info.size;
+                        suballocations.push(Suballocation::Allocation(chunk.chunk_type, info));
+                    }
+                    _ => {
+                        let chunk_end = chunk.offset + chunk.size;
+                        if let Some(Suballocation::Free { offset, size }) =
+                            suballocations.last_mut()
+                        {
+                            if *offset + *size == end {
+                                *size = chunk_end - *offset;
+                                end = chunk_end;
+                                continue;
+                            }
+                        }
+                        suballocations.push(Suballocation::Free {
+                            offset: end,
+                            size: chunk_end - end,
+                        });
+                        end = chunk_end;
+                    }
+                }
+            }
+            if block.size > end {
+                suballocations.push(Suballocation::Free {
+                    offset: end,
+                    size: block.size - end,
+                });
+            }
+
+            let mut allocation_count: usize = 0;
+            let mut allocation_bytes: vk::DeviceSize = 0;
+            let mut unused_range_count: usize = 0;
+            for suballocation in suballocations.iter() {
+                match suballocation {
+                    Suballocation::Allocation(_, info) => {
+                        allocation_count += 1;
+                        allocation_bytes += info.size;
+                    }
+                    Suballocation::Free { .. } => unused_range_count += 1,
+                }
+            }
+
+            json.key(&index.to_string());
+            json.begin_object();
+            json.key("MapRefs");
+            json.number(0);
+            json.key("TotalBytes");
+            json.number(block.size);
+            json.key("UnusedBytes");
+            json.number(block.size - allocation_bytes);
+            json.key("Allocations");
+            json.number(allocation_count);
+            json.key("UnusedRanges");
+            json.number(unused_range_count);
+
+            json.key("Suballocations");
+            json.begin_array();
+            for suballocation in suballocations {
+                json.begin_object();
+                match suballocation {
+                    Suballocation::Allocation(chunk_type, info) => {
+                        json.key("Offset");
+                        json.number(info.offset);
+                        json.key("Type");
+                        json.string(chunk_type.dump_name());
+                        json.key("Size");
+                        json.number(info.size);
+                        if info.tag != 0 {
+                            json.key("CustomData");
+                            json.string(&format!("{:#x}", info.tag));
+                        }
+                        if let Some(name) = info.name.as_deref() {
+                            json.key("Name");
+                            json.string(name);
+                        }
+                        #[cfg(feature = "backtrace")]
+                        if let Some(backtrace) = &info.backtrace {
+                            json.key("Backtrace");
+                            json.string(&backtrace.to_string());
+                        }
+                    }
+                    Suballocation::Free { offset, size } => {
+                        json.key("Offset");
+                        json.number(offset);
+                        json.key("Type");
+                        json.string(ChunkType::Free.dump_name());
+                        json.key("Size");
+                        json.number(size);
+                    }
+                }
+                json.end_object();
+            }
+            json.end_array();
+
+            json.end_object();
+        }
+        json.end_object();
+
+        json.key("DedicatedAllocations");
+        json.begin_array();
+        for block in self.blocks.iter().flatten() {
+            if !block.is_dedicated {
+                continue;
+            }
+            json.begin_object();
+            json.key("Type");
+            json.string("UNKNOWN");
+            json.key("Size");
+            json.number(block.size);
+            if block.label.tag != 0 {
+                json.key("CustomData");
+                json.string(&format!("{:#x}", block.label.tag));
+            }
+            if let Some(name) = block.label.name.as_deref() {
+                json.key("Name");
+                json.string(name);
+            }
+            #[cfg(feature = "backtrace")]
+            if let Some(backtrace) = &block.label.backtrace {
+                json.key("Backtrace");
+                json.string(&backtrace.to_string());
+            }
+            json.end_object();
+        }
+        json.end_array();
+
+        json.end_object();
+
+        Ok(())
+    }
+}
+
+/// An entry of the suballocation list of a block in the dump.
+enum Suballocation<LT: Lifetime> {
+    Allocation(ChunkType, AllocationInfo<LT>),
+    Free {
+        offset: vk::DeviceSize,
+        size: vk::DeviceSize,
+    },
+}
+
+impl ChunkType {
+    /// The name of the VMA suballocation type of the chunk type.
+    #[inline]
+    fn dump_name(self) -> &'static str {
+        match self {
+            ChunkType::Free => "FREE",
+            ChunkType::Linear => "BUFFER",
+            ChunkType::Optimal => "IMAGE_OPTIMAL",
+        }
+    }
+}
+
+/// Writes the statistics in the layout of VMA's detailed statistics.
+fn write_statistics(json: &mut JsonWriter, statistics: &Statistics) {
+    json.begin_object();
+    json.key("BlockCount");
+    json.number(statistics.block_count);
+    json.key("BlockBytes");
+    json.number(statistics.block_bytes);
+    json.key("AllocationCount");
+    json.number(statistics.allocation_count);
+    json.key("AllocationBytes");
+    json.number(statistics.allocation_bytes);
+    json.end_object();
+}
+
+/// A minimal JSON writer. Takes care of the separators and string escaping.
+#[derive(Debug, Default)]
+struct JsonWriter {
+    output: String,
+    // For each open object or array, if the next value needs a leading comma.
+    scopes: Vec<bool>,
+    after_key: bool,
+}
+
+impl JsonWriter {
+    fn begin_object(&mut self) {
+        self.begin_value();
+        self.output.push('{');
+        self.scopes.push(false);
+    }
+
+    fn end_object(&mut self) {
+        self.scopes.pop();
+        self.output.push('}');
+    }
+
+    fn begin_array(&mut self) {
+        self.begin_value();
+        self.output.push('[');
+        self.scopes.push(false);
+    }
+
+    fn end_array(&mut self) {
+        self.scopes.pop();
+        self.output.push(']');
+    }
+
+    fn key(&mut self, key: &str) {
+        self.string(key);
+        self.output.push(':');
+        self.after_key = true;
+    }
+
+    fn string(&mut self, value: &str) {
+        self.begin_value();
+        self.output.push('"');
+        for c in value.chars() {
+            match c {
+                '"' => self.output.push_str("\\\""),
+                '\\' => self.output.push_str("\\\\"),
+                '\n' => self.output.push_str("\\n"),
+                '\r' => self.output.push_str("\\r"),
+                '\t' => self.output.push_str("\\t"),
+                c if c.is_control() => {
+                    self.output.push_str(&format!("\\u{:04x}", u32::from(c)));
+                }
+                c => self.output.push(c),
+            }
+        }
+        self.output.push('"');
+    }
+
+    fn number(&mut self, value: impl Display) {
+        self.begin_value();
+        self.output.push_str(&value.to_string());
+    }
+
+    fn begin_value(&mut self) {
+        if self.after_key {
+            self.after_key = false;
+            return;
+        }
+        if let Some(needs_comma) = self.scopes.last_mut() {
+            if *needs_comma {
+                self.output.push(',');
+            }
+            *needs_comma = true;
+        }
+    }
+
+    fn finish(self) -> String {
+        self.output
+    }
+}
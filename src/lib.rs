@@ -9,80 +9,513 @@
 //! reducing the fragmentation.
 //!
 //! ## Example:
-//! ```ignore
+//! ```no_run
+//! use ash::vk;
+//! use ash_alloc::{AllocationDescriptor, Allocator, AllocatorDescriptor, MemoryLocation};
+//!
 //! #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 //! enum Lifetime {
 //!     Buffer,
 //!     Image,
 //! }
 //!
-//! impl vk_alloc::Lifetime for Lifetime {}
+//! impl ash_alloc::Lifetime for Lifetime {}
 //!
+//! # fn example(
+//! #     instance: &ash::Instance,
+//! #     physical_device: vk::PhysicalDevice,
+//! #     logical_device: &ash::Device,
+//! # ) {
 //! unsafe {
-//!     Allocator::<Lifetime>::new(
-//!         &instance,
-//!         &physical_device,
+//!     let alloc = Allocator::<Lifetime>::new(
+//!         instance,
+//!         physical_device,
 //!         &AllocatorDescriptor {
 //!             ..Default::default()
 //!         },
-//!     ).unwrap();
+//!     )
+//!     .unwrap();
 //!
 //!     let allocation = alloc
 //!         .allocate(
-//!             &logical_device,
+//!             logical_device,
 //!             &AllocationDescriptor {
 //!                 location: MemoryLocation::GpuOnly,
 //!                 requirements: vk::MemoryRequirements::default()
 //!                     .alignment(512)
 //!                     .size(1024)
-//!                     .memory_type_bits(u32::MAX)
-//!                     .build(),
+//!                     .memory_type_bits(u32::MAX),
 //!                 lifetime: Lifetime::Buffer,
 //!                 is_dedicated: false,
 //!                 is_optimal: false,
+//!                 device_mask: 0,
+//!                 memory_hook: None,
+//!                 name: None,
+//!                 tag: 0,
 //!             },
 //!         )
 //!         .unwrap();
+//!
+//!     alloc.deallocate(logical_device, &allocation).unwrap();
+//!     alloc.cleanup(logical_device);
 //! }
+//! # }
 //! ```
 //!
+#[cfg(feature = "backtrace")]
+use std::backtrace::Backtrace;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::convert::TryInto;
-use std::ffi::c_void;
+use std::ffi::{c_void, CString};
 use std::fmt::Debug;
 use std::hash::Hash;
 use std::num::NonZeroUsize;
 use std::ptr;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
 
+use ash::prelude::VkResult;
 use ash::vk;
 #[cfg(feature = "tracing")]
 use ash::vk::Handle;
-use parking_lot::{Mutex, RwLock};
+use parking_lot::{MappedRwLockReadGuard, Mutex, RwLock, RwLockReadGuard};
 #[cfg(feature = "tracing")]
-use tracing1::{debug, info};
+use tracing1::{debug, info, warn};
 
+pub use device_fault::DeviceFaultAttribution;
 pub use error::AllocatorError;
+pub use owned::OwnedAllocation;
+pub use snapshot::{AllocationSnapshot, SnapshotDiff};
 
+mod device_fault;
 mod error;
+mod json;
+mod managed;
+mod owned;
+mod snapshot;
 
 type Result<T> = std::result::Result<T, AllocatorError>;
 
+/// Pools are keyed by the lifetime and the device mask of the allocations.
+type PoolKey<LT> = (LT, u32);
+
+/// The pools of a lifetime, one for each memory type.
+type LifetimePools<LT> = Vec<Mutex<MemoryPool<LT>>>;
+
 /// For a minimal bucket size of 256b as log2.
 const MINIMAL_BUCKET_SIZE_LOG2: u32 = 8;
 
+/// The pattern the debug margins around allocations are filled with.
+const DEBUG_MARGIN_PATTERN: [u8; 4] = 0x7F84_E666u32.to_le_bytes();
+
 /// The lifetime of an allocation. Used to pool allocations and reduce fragmentation.
 pub trait Lifetime: Debug + Copy + Hash + Eq + PartialEq {}
 
+/// Information about the memory block a `MemoryHook` is called for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryBlockInfo {
+    /// The memory type index of the memory block.
+    pub memory_type_index: u32,
+    /// The size of the memory block.
+    pub size: vk::DeviceSize,
+    /// True if the memory block holds a dedicated allocation.
+    pub is_dedicated: bool,
+    /// The device mask the memory block is allocated with. 0 if no device mask is used.
+    pub device_mask: u32,
+}
+
+/// A hook into the creation and destruction of the `DeviceMemory` of memory blocks.
+///
+/// Hooks can be set per lifetime with `Allocator::set_memory_hook` or per allocation with
+/// `AllocationDescriptor::memory_hook`.
+pub trait MemoryHook: Debug + Send + Sync {
+    /// Allocates the `DeviceMemory` of a memory block. The hook can extend the pNext chain of `info`
+    /// with additional structures and needs to call `allocate` with the extended info.
+    ///
+    /// ```ignore
+    /// fn allocate_memory(
+    ///     &self,
+    ///     info: vk::MemoryAllocateInfo<'_>,
+    ///     _block_info: &MemoryBlockInfo,
+    ///     allocate: &mut dyn FnMut(&vk::MemoryAllocateInfo<'_>) -> VkResult<vk::DeviceMemory>,
+    /// ) -> VkResult<vk::DeviceMemory> {
+    ///     let mut priority_info = vk::MemoryPriorityAllocateInfoEXT::default().priority(1.0);
+    ///     allocate(&info.push_next(&mut priority_info))
+    /// }
+    /// ```
+    fn allocate_memory(
+        &self,
+        info: vk::MemoryAllocateInfo<'_>,
+        block_info: &MemoryBlockInfo,
+        allocate: &mut dyn FnMut(&vk::MemoryAllocateInfo<'_>) -> VkResult<vk::DeviceMemory>,
+    ) -> VkResult<vk::DeviceMemory> {
+        let _ = block_info;
+        allocate(&info)
+    }
+
+    /// Called right after the `DeviceMemory` of a memory block was created and mapped.
+    fn after_allocate(&self, device_memory: vk::DeviceMemory, block_info: &MemoryBlockInfo) {
+        let _ = (device_memory, block_info);
+    }
+
+    /// Called right before the `DeviceMemory` of a memory block is unmapped and freed.
+    fn before_free(&self, device_memory: vk::DeviceMemory, block_info: &MemoryBlockInfo) {
+        let _ = (device_memory, block_info);
+    }
+}
+
+/// Information about a `DeviceMemory` that the allocator allocated or is about to free.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceMemoryInfo<LT: Lifetime> {
+    /// The `DeviceMemory` of the memory block.
+    pub device_memory: vk::DeviceMemory,
+    /// The memory type index of the memory block.
+    pub memory_type_index: u32,
+    /// The heap index of the memory type.
+    pub heap_index: u32,
+    /// The size of the memory block.
+    pub size: vk::DeviceSize,
+    /// True if the memory block holds a dedicated allocation.
+    pub is_dedicated: bool,
+    /// The lifetime of the pool the memory block belongs to.
+    pub lifetime: LT,
+}
+
+/// Information about an allocation that is still alive.
+#[derive(Clone, Debug)]
+pub struct AllocationInfo<LT: Lifetime> {
+    /// The id of the allocation. Unique over the lifetime of the allocator.
+    pub id: u64,
+    /// The lifetime of the allocation.
+    pub lifetime: LT,
+    /// The memory type index of the allocation.
+    pub memory_type_index: u32,
+    /// The memory heap index of the allocation.
+    pub heap_index: u32,
+    /// The `DeviceMemory` the allocation is placed in.
+    pub device_memory: vk::DeviceMemory,
+    /// The offset of the allocation inside the `DeviceMemory`.
+    pub offset: vk::DeviceSize,
+    /// The size of the allocation.
+    pub size: vk::DeviceSize,
+    /// True if the allocation has its own `DeviceMemory`.
+    pub is_dedicated: bool,
+    /// True if the memory of the allocation is persistently mapped.
+    pub is_mapped: bool,
+    /// The device address registered for the allocation.
+    pub device_address: Option<vk::DeviceAddress>,
+    /// The name of the allocation.
+    pub name: Option<Cow<'static, str>>,
+    /// The user defined tag of the allocation.
+    pub tag: u64,
+    /// The backtrace of the `Allocator::allocate` call that created the allocation. Captured with
+    /// `Backtrace::force_capture` on every allocation. Ignored when comparing allocation infos.
+    #[cfg(feature = "backtrace")]
+    pub backtrace: Option<Arc<Backtrace>>,
+}
+
+impl<LT: Lifetime> PartialEq for AllocationInfo<LT> {
+    fn eq(&self, other: &Self) -> bool {
+        // Backtraces can't be compared.
+        self.id == other.id
+            && self.lifetime == other.lifetime
+            && self.memory_type_index == other.memory_type_index
+            && self.heap_index == other.heap_index
+            && self.device_memory == other.device_memory
+            && self.offset == other.offset
+            && self.size == other.size
+            && self.is_dedicated == other.is_dedicated
+            && self.is_mapped == other.is_mapped
+            && self.device_address == other.device_address
+            && self.name == other.name
+            && self.tag == other.tag
+    }
+}
+
+impl<LT: Lifetime> Eq for AllocationInfo<LT> {}
+
+/// What occupies an offset inside a `DeviceMemory` of the allocator.
+#[derive(Clone, Debug)]
+pub enum AllocationLookup<LT: Lifetime> {
+    /// The offset lies inside of an allocation.
+    Allocation(AllocationInfo<LT>),
+    /// The offset lies inside of a free range.
+    Free {
+        /// The offset of the free range inside the `DeviceMemory`.
+        offset: vk::DeviceSize,
+        /// The size of the free range.
+        size: vk::DeviceSize,
+    },
+    /// The offset lies inside of the alignment padding or the debug margins between allocations.
+    Padding,
+}
+
+/// Statistics of the memory blocks and allocations of a part of the allocator.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Statistics {
+    /// Number of Vulkan memory blocks, including the blocks of dedicated allocations.
+    pub block_count: usize,
+    /// Bytes of all Vulkan memory blocks.
+    pub block_bytes: vk::DeviceSize,
+    /// Number of allocations, including dedicated allocations.
+    pub allocation_count: usize,
+    /// Bytes used by allocations, including dedicated allocations.
+    pub allocation_bytes: vk::DeviceSize,
+    /// Number of dedicated allocations.
+    pub dedicated_allocation_count: usize,
+    /// Bytes used by dedicated allocations.
+    pub dedicated_allocation_bytes: vk::DeviceSize,
+    /// Bytes inside the memory blocks that are not used by allocations. Includes the padding
+    /// between allocations, but not the debug margins around them.
+    pub free_bytes: vk::DeviceSize,
+    /// Size of the largest free range that can be sub allocated, without the debug margins a new
+    /// allocation needs.
+    pub largest_free_range: vk::DeviceSize,
+}
+
+impl Statistics {
+    /// Adds the statistics of another part of the allocator.
+    fn add(&mut self, other: &Statistics) {
+        self.block_count += other.block_count;
+        self.block_bytes += other.block_bytes;
+        self.allocation_count += other.allocation_count;
+        self.allocation_bytes += other.allocation_bytes;
+        self.dedicated_allocation_count += other.dedicated_allocation_count;
+        self.dedicated_allocation_bytes += other.dedicated_allocation_bytes;
+        self.free_bytes += other.free_bytes;
+        self.largest_free_range = self.largest_free_range.max(other.largest_free_range);
+    }
+}
+
+/// A snapshot of the statistics of the allocator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllocatorStatistics<LT: Lifetime> {
+    /// Statistics over all pools.
+    pub total: Statistics,
+    /// Statistics for each memory heap, indexed by the heap index.
+    pub heaps: Vec<Statistics>,
+    /// Statistics for each memory type, indexed by the memory type index.
+    pub memory_types: Vec<Statistics>,
+    /// Statistics for each lifetime that has pools.
+    pub lifetimes: HashMap<LT, Statistics>,
+    /// Histograms of the allocation requests for each lifetime and memory type index since the
+    /// allocator was created or the histograms were reset.
+    pub requests: HashMap<(LT, u32), RequestHistogram>,
+}
+
+/// Histograms of the sizes and alignments of allocation requests. Bucket 0 counts values of up
+/// to 512, every following bucket counts values of up to twice the size, like the buckets of the
+/// free list.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestHistogram {
+    /// Number of requests for each size bucket.
+    pub sizes: Vec<usize>,
+    /// Number of requests for each alignment bucket.
+    pub alignments: Vec<usize>,
+}
+
+impl RequestHistogram {
+    /// Counts a request.
+    fn record(&mut self, size: vk::DeviceSize, alignment: vk::DeviceSize) -> Result<()> {
+        increment_bucket(&mut self.sizes, size)?;
+        increment_bucket(&mut self.alignments, alignment)
+    }
+
+    /// Adds the histograms of another part of the allocator.
+    fn add(&mut self, other: &RequestHistogram) {
+        add_buckets(&mut self.sizes, &other.sizes);
+        add_buckets(&mut self.alignments, &other.alignments);
+    }
+}
+
+/// The fragmentation of the free chunks of a part of the allocator.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Fragmentation {
+    /// Bytes in free chunks that can be allocated. Doesn't include the padding between
+    /// allocations and the debug margins a new allocation in each chunk needs.
+    pub free_bytes: vk::DeviceSize,
+    /// Size of the largest free chunk without the debug margins a new allocation needs. Matches
+    /// `Statistics::largest_free_range`.
+    pub largest_free_range: vk::DeviceSize,
+    /// Number of free chunks in each bucket of the free list. Bucket 0 holds chunks of up to
+    /// 512 bytes, every following bucket holds chunks of up to twice the size.
+    pub free_chunks_per_bucket: Vec<usize>,
+}
+
+impl Fragmentation {
+    /// The fragmentation score `1 - largest_free_range / free_bytes`. Is 0 if all free memory is
+    /// one contiguous range and approaches 1 the more the free memory is split up.
+    pub fn score(&self) -> f64 {
+        if self.free_bytes == 0 {
+            return 0.0;
+        }
+        // Precision loss is fine for a ratio.
+        #[allow(clippy::as_conversions)]
+        let ratio = self.largest_free_range as f64 / self.free_bytes as f64;
+        1.0 - ratio
+    }
+
+    /// Adds the fragmentation of another part of the allocator.
+    fn add(&mut self, other: &Fragmentation) {
+        self.free_bytes += other.free_bytes;
+        self.largest_free_range = self.largest_free_range.max(other.largest_free_range);
+        add_buckets(
+            &mut self.free_chunks_per_bucket,
+            &other.free_chunks_per_bucket,
+        );
+    }
+}
+
+/// The fragmentation of a memory pool.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PoolFragmentation<LT: Lifetime> {
+    /// The lifetime of the pool.
+    pub lifetime: LT,
+    /// The device mask of the pool.
+    pub device_mask: u32,
+    /// The memory type index of the pool.
+    pub memory_type_index: u32,
+    /// The fragmentation of the pool.
+    pub fragmentation: Fragmentation,
+}
+
+/// A snapshot of the fragmentation of the allocator.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AllocatorFragmentation<LT: Lifetime> {
+    /// Fragmentation over all pools.
+    pub total: Fragmentation,
+    /// Fragmentation of each pool that has memory blocks.
+    pub pools: Vec<PoolFragmentation<LT>>,
+}
+
+/// A callback that is called with the information about a `DeviceMemory`.
+pub type DeviceMemoryCallback<LT> = Arc<dyn Fn(&DeviceMemoryInfo<LT>) + Send + Sync>;
+
+/// A callback that is called with the allocations that were still alive when a managed allocator
+/// was dropped.
+pub type LeakCallback<LT> = Arc<dyn Fn(&[AllocationInfo<LT>]) + Send + Sync>;
+
+/// Callbacks that are called when the allocator calls `vkAllocateMemory` or `vkFreeMemory`.
+/// Set them with `AllocatorDescriptor::device_memory_callbacks`.
+///
+/// The pool of the memory block is locked while a callback runs, so the callbacks must not call
+/// into the allocator.
+pub struct DeviceMemoryCallbacks<LT: Lifetime> {
+    /// Called after the `DeviceMemory` of a memory block was allocated.
+    pub allocate: Option<DeviceMemoryCallback<LT>>,
+    /// Called before the `DeviceMemory` of a memory block is freed.
+    pub free: Option<DeviceMemoryCallback<LT>>,
+}
+
+impl<LT: Lifetime> Clone for DeviceMemoryCallbacks<LT> {
+    fn clone(&self) -> Self {
+        Self {
+            allocate: self.allocate.clone(),
+            free: self.free.clone(),
+        }
+    }
+}
+
+impl<LT: Lifetime> Default for DeviceMemoryCallbacks<LT> {
+    fn default() -> Self {
+        Self {
+            allocate: None,
+            free: None,
+        }
+    }
+}
+
+impl<LT: Lifetime> Debug for DeviceMemoryCallbacks<LT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceMemoryCallbacks")
+            .field("allocate", &self.allocate.is_some())
+            .field("free", &self.free.is_some())
+            .finish()
+    }
+}
+
 /// Describes the configuration of an `Allocator`.
-#[derive(Debug, Clone)]
-pub struct AllocatorDescriptor {
+#[derive(Clone)]
+pub struct AllocatorDescriptor<LT: Lifetime> {
     /// The size of the blocks that are allocated. Defined as log2(size in bytes). Default: 64 MiB.
     pub block_size: u8,
+    /// Callbacks for every `DeviceMemory` the allocator allocates and frees.
+    pub device_memory_callbacks: Option<DeviceMemoryCallbacks<LT>>,
+    /// Host allocation callbacks that are passed to every Vulkan call of the allocator that
+    /// accepts them. The callbacks need to stay valid as long as the allocator exists.
+    pub allocation_callbacks: Option<vk::AllocationCallbacks<'static>>,
+    /// If set, every `DeviceMemory` the allocator creates gets an object name through
+    /// `VK_EXT_debug_utils`, so memory blocks can be identified in debugging tools.
+    pub debug_utils: Option<ash::ext::debug_utils::Device>,
+    /// Size in bytes of the guard margins that are reserved before and after every sub
+    /// allocation in host visible memory. The margins are filled with a magic pattern that is
+    /// verified on deallocation and by `Allocator::check_corruption`. 0 disables the margins.
+    pub debug_margin: vk::DeviceSize,
+    /// If set, sub allocations in host visible memory are filled with a byte pattern when they
+    /// are allocated and when they are freed.
+    pub debug_fill: Option<DebugFillPatterns>,
+    /// Validates the internal state of a pool after every allocation and deallocation on it and
+    /// panics if it's invalid. Only used in debug builds. Slow.
+    pub validate_operations: bool,
+    /// Called with the leaked allocations when a managed allocator is dropped while allocations
+    /// are still alive.
+    pub leak_callback: Option<LeakCallback<LT>>,
+}
+
+impl<LT: Lifetime> Default for AllocatorDescriptor<LT> {
+    fn default() -> Self {
+        Self {
+            block_size: 26,
+            device_memory_callbacks: None,
+            allocation_callbacks: None,
+            debug_utils: None,
+            debug_margin: 0,
+            debug_fill: None,
+            validate_operations: false,
+            leak_callback: None,
+        }
+    }
+}
+
+impl<LT: Lifetime> Debug for AllocatorDescriptor<LT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AllocatorDescriptor")
+            .field("block_size", &self.block_size)
+            .field("device_memory_callbacks", &self.device_memory_callbacks)
+            .field("allocation_callbacks", &self.allocation_callbacks)
+            .field(
+                "debug_utils",
+                &self
+                    .debug_utils
+                    .as_ref()
+                    .map(|debug_utils| debug_utils.device()),
+            )
+            .field("debug_margin", &self.debug_margin)
+            .field("debug_fill", &self.debug_fill)
+            .field("validate_operations", &self.validate_operations)
+            .field("leak_callback", &self.leak_callback.is_some())
+            .finish()
+    }
+}
+
+/// The byte patterns host visible sub allocations are filled with to make reads of
+/// uninitialized or freed memory visible.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DebugFillPatterns {
+    /// The pattern a sub allocation is filled with when it's allocated.
+    pub allocate: u8,
+    /// The pattern a sub allocation is filled with when it's freed.
+    pub free: u8,
 }
 
-impl Default for AllocatorDescriptor {
+impl Default for DebugFillPatterns {
     fn default() -> Self {
-        Self { block_size: 26 }
+        Self {
+            allocate: 0xDC,
+            free: 0xEF,
+        }
     }
 }
 
@@ -91,11 +524,19 @@ impl Default for AllocatorDescriptor {
 pub struct Allocator<LT: Lifetime> {
     driver_id: vk::DriverId,
     is_integrated: bool,
-    pools: RwLock<HashMap<LT, Vec<Mutex<MemoryPool>>>>,
+    pools: RwLock<HashMap<PoolKey<LT>, LifetimePools<LT>>>,
+    memory_hooks: RwLock<HashMap<LT, Arc<dyn MemoryHook>>>,
     block_size: vk::DeviceSize,
     memory_types: Vec<vk::MemoryType>,
     memory_properties: vk::PhysicalDeviceMemoryProperties,
     buffer_image_granularity: u64,
+    settings: PoolSettings<LT>,
+    handles: Option<managed::DeviceHandles<LT>>,
+    next_allocation_id: AtomicU64,
+    // Changes with every cleanup. Part of every slot key.
+    epoch: AtomicU32,
+    #[cfg_attr(not(debug_assertions), allow(dead_code))]
+    validate_operations: bool,
 }
 
 impl<LT: Lifetime> Allocator<LT> {
@@ -107,7 +548,7 @@ impl<LT: Lifetime> Allocator<LT> {
     pub unsafe fn new(
         instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
-        descriptor: &AllocatorDescriptor,
+        descriptor: &AllocatorDescriptor<LT>,
     ) -> Result<Self> {
         let (driver_id, is_integrated, buffer_image_granularity) =
             query_driver(instance, physical_device);
@@ -129,10 +570,26 @@ impl<LT: Lifetime> Allocator<LT> {
             driver_id,
             is_integrated,
             pools: RwLock::default(),
+            memory_hooks: RwLock::default(),
             block_size,
             memory_types,
             memory_properties,
             buffer_image_granularity,
+            settings: PoolSettings {
+                device_memory: descriptor
+                    .device_memory_callbacks
+                    .clone()
+                    .unwrap_or_default(),
+                allocation: descriptor.allocation_callbacks,
+                debug_utils: descriptor.debug_utils.clone().map(DebugUtils),
+                debug_margin: descriptor.debug_margin,
+                debug_fill: descriptor.debug_fill,
+                counters: Arc::new(UsageCounters::default()),
+            },
+            handles: None,
+            next_allocation_id: AtomicU64::new(1),
+            epoch: AtomicU32::new(0),
+            validate_operations: descriptor.validate_operations,
         })
     }
 
@@ -147,6 +604,48 @@ impl<LT: Lifetime> Allocator<LT> {
         buffer: vk::Buffer,
         location: MemoryLocation,
         lifetime: LT,
+    ) -> Result<Allocation<LT>> {
+        self.allocate_memory_for_buffer_with_device_mask(device, buffer, location, lifetime, 0)
+    }
+
+    /// Allocates memory for a buffer on the physical devices of a device group given by the
+    /// device mask. 0 allocates the memory on all physical devices of the device group.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided device and buffer are in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_memory_for_buffer_with_device_mask(
+        &self,
+        device: &ash::Device,
+        buffer: vk::Buffer,
+        location: MemoryLocation,
+        lifetime: LT,
+        device_mask: u32,
+    ) -> Result<Allocation<LT>> {
+        self.allocate_memory_for_buffer_with_descriptor(
+            device,
+            buffer,
+            &ResourceAllocationDescriptor {
+                location,
+                lifetime,
+                device_mask,
+                name: None,
+                tag: 0,
+            },
+        )
+    }
+
+    /// Allocates memory for a buffer with the location, lifetime, device mask, name and tag of
+    /// the descriptor.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided device and buffer are in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_memory_for_buffer_with_descriptor(
+        &self,
+        device: &ash::Device,
+        buffer: vk::Buffer,
+        descriptor: &ResourceAllocationDescriptor<LT>,
     ) -> Result<Allocation<LT>> {
         let info = vk::BufferMemoryRequirementsInfo2::default().buffer(buffer);
         let mut dedicated_requirements = vk::MemoryDedicatedRequirements::default();
@@ -162,10 +661,14 @@ impl<LT: Lifetime> Allocator<LT> {
 
         let alloc_decs = AllocationDescriptor {
             requirements: memory_requirements,
-            location,
-            lifetime,
+            location: descriptor.location,
+            lifetime: descriptor.lifetime,
             is_dedicated,
             is_optimal: false,
+            device_mask: descriptor.device_mask,
+            memory_hook: None,
+            name: descriptor.name.clone(),
+            tag: descriptor.tag,
         };
 
         self.allocate(device, &alloc_decs)
@@ -183,6 +686,55 @@ impl<LT: Lifetime> Allocator<LT> {
         location: MemoryLocation,
         lifetime: LT,
         is_optimal: bool,
+    ) -> Result<Allocation<LT>> {
+        self.allocate_memory_for_image_with_device_mask(
+            device, image, location, lifetime, is_optimal, 0,
+        )
+    }
+
+    /// Allocates memory for an image on the physical devices of a device group given by the
+    /// device mask. 0 allocates the memory on all physical devices of the device group.
+    /// `is_optimal` must be set true if the image is a optimal image (a regular texture).
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided device and image are in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_memory_for_image_with_device_mask(
+        &self,
+        device: &ash::Device,
+        image: vk::Image,
+        location: MemoryLocation,
+        lifetime: LT,
+        is_optimal: bool,
+        device_mask: u32,
+    ) -> Result<Allocation<LT>> {
+        self.allocate_memory_for_image_with_descriptor(
+            device,
+            image,
+            is_optimal,
+            &ResourceAllocationDescriptor {
+                location,
+                lifetime,
+                device_mask,
+                name: None,
+                tag: 0,
+            },
+        )
+    }
+
+    /// Allocates memory for an image with the location, lifetime, device mask, name and tag of
+    /// the descriptor. `is_optimal` must be set true if the image is a optimal image (a regular
+    /// texture).
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided device and image are in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_memory_for_image_with_descriptor(
+        &self,
+        device: &ash::Device,
+        image: vk::Image,
+        is_optimal: bool,
+        descriptor: &ResourceAllocationDescriptor<LT>,
     ) -> Result<Allocation<LT>> {
         let info = vk::ImageMemoryRequirementsInfo2::default().image(image);
         let mut dedicated_requirements = vk::MemoryDedicatedRequirements::default();
@@ -198,15 +750,71 @@ impl<LT: Lifetime> Allocator<LT> {
 
         let alloc_decs = AllocationDescriptor {
             requirements: memory_requirements,
-            location,
-            lifetime,
+            location: descriptor.location,
+            lifetime: descriptor.lifetime,
             is_dedicated,
             is_optimal,
+            device_mask: descriptor.device_mask,
+            memory_hook: None,
+            name: descriptor.name.clone(),
+            tag: descriptor.tag,
         };
 
         self.allocate(device, &alloc_decs)
     }
 
+    /// Binds the memory of the allocation to a buffer. The device group info can be used to bind
+    /// the buffer to the memory instances of specific physical devices in a device group.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided device, buffer and allocation are in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn bind_buffer_memory(
+        &self,
+        device: &ash::Device,
+        buffer: vk::Buffer,
+        allocation: &Allocation<LT>,
+        device_group_info: Option<&mut vk::BindBufferMemoryDeviceGroupInfo<'_>>,
+    ) -> Result<()> {
+        let mut info = vk::BindBufferMemoryInfo::default()
+            .buffer(buffer)
+            .memory(allocation.device_memory)
+            .memory_offset(allocation.offset);
+        if let Some(device_group_info) = device_group_info {
+            info = info.push_next(device_group_info);
+        }
+
+        device
+            .bind_buffer_memory2(&[info])
+            .map_err(|_| AllocatorError::FailedToBind)
+    }
+
+    /// Binds the memory of the allocation to an image. The device group info can be used to bind
+    /// the image to the memory instances of specific physical devices in a device group.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided device, image and allocation are in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn bind_image_memory(
+        &self,
+        device: &ash::Device,
+        image: vk::Image,
+        allocation: &Allocation<LT>,
+        device_group_info: Option<&mut vk::BindImageMemoryDeviceGroupInfo<'_>>,
+    ) -> Result<()> {
+        let mut info = vk::BindImageMemoryInfo::default()
+            .image(image)
+            .memory(allocation.device_memory)
+            .memory_offset(allocation.offset);
+        if let Some(device_group_info) = device_group_info {
+            info = info.push_next(device_group_info);
+        }
+
+        device
+            .bind_image_memory2(&[info])
+            .map_err(|_| AllocatorError::FailedToBind)
+    }
+
     /// Allocates memory on the allocator.
     ///
     /// # Safety
@@ -222,8 +830,11 @@ impl<LT: Lifetime> Allocator<LT> {
 
         #[cfg(feature = "tracing")]
         debug!(
-            "Allocating {} bytes with an alignment of {}.",
-            size, alignment
+            "Allocating {} bytes with an alignment of {} for {:?} (tag {}).",
+            size,
+            alignment,
+            descriptor.name.as_deref().unwrap_or("<unnamed>"),
+            descriptor.tag
         );
 
         if size == 0 || !alignment.is_power_of_two() {
@@ -235,61 +846,238 @@ impl<LT: Lifetime> Allocator<LT> {
             descriptor.requirements.memory_type_bits,
         )?;
 
-        let has_key = self.pools.read().contains_key(&descriptor.lifetime);
-        if !has_key {
-            let mut pools = Vec::with_capacity(self.memory_types.len());
-            for (i, memory_type) in self.memory_types.iter().enumerate() {
-                let pool = MemoryPool::new(
-                    self.block_size,
-                    i.try_into()?,
-                    memory_type
-                        .property_flags
-                        .contains(vk::MemoryPropertyFlags::HOST_VISIBLE),
-                )?;
-                pools.push(Mutex::new(pool));
-            }
-
-            self.pools.write().insert(descriptor.lifetime, pools);
-        }
-
-        let lifetime_pools = self.pools.read();
-
-        let pool = &lifetime_pools
-            .get(&descriptor.lifetime)
-            .ok_or_else(|| {
-                AllocatorError::Internal(format!(
-                    "can't find pool for lifetime {:?}",
-                    descriptor.lifetime
-                ))
-            })?
-            .get(memory_type_index)
-            .ok_or_else(|| {
-                AllocatorError::Internal(format!(
-                    "can't find memory_type {} in pool {:?}",
-                    memory_type_index, descriptor.lifetime
-                ))
-            })?;
+        let lifetime_pools = self.lifetime_pools(descriptor.lifetime, descriptor.device_mask)?;
+        let pool = get_pool(&lifetime_pools, descriptor.lifetime, memory_type_index)?;
+        let pool_hook = self.memory_hooks.read().get(&descriptor.lifetime).cloned();
+        let label = AllocationLabel {
+            id: self.next_allocation_id(),
+            name: descriptor.name.clone(),
+            tag: descriptor.tag,
+            device_address: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Some(Arc::new(Backtrace::force_capture())),
+        };
 
-        if descriptor.is_dedicated || size >= self.block_size {
+        let mut pool = pool.lock();
+        pool.requests.record(size, alignment)?;
+        // An allocation with its own hook needs its own memory block.
+        let allocation = if descriptor.is_dedicated
+            || descriptor.memory_hook.is_some()
+            || pool.reserved_size(size, alignment) >= self.block_size
+        {
             #[cfg(feature = "tracing")]
             debug!(
                 "Allocating as dedicated block on memory type {}",
                 memory_type_index
             );
-            pool.lock()
-                .allocate_dedicated(device, size, descriptor.lifetime)
+            let hook = descriptor.memory_hook.as_ref().or(pool_hook.as_ref());
+            pool.allocate_dedicated(device, size, None, hook, label)
         } else {
             #[cfg(feature = "tracing")]
             debug!("Sub allocating on memory type {}", memory_type_index);
-            pool.lock().allocate(
+            pool.allocate(
                 device,
-                self.buffer_image_granularity,
                 size,
                 alignment,
-                descriptor.lifetime,
                 descriptor.is_optimal,
+                pool_hook.as_ref(),
+                label,
             )
+        };
+        self.validate_after_operation(&pool);
+
+        allocation
+    }
+
+    /// Imports memory from a POSIX file descriptor (for example a dma-buf) as a dedicated allocation.
+    ///
+    /// The memory type is chosen from the memory types reported for the file descriptor and the
+    /// `memory_type_bits` of the descriptor. For `OPAQUE_FD` handles the driver can't report the
+    /// memory types, so `memory_type_bits` must only contain the memory type that was used when
+    /// exporting the memory. On success the ownership of the file descriptor is transferred to the
+    /// Vulkan implementation.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided device and file descriptor are in a valid state
+    /// and that the `VK_KHR_external_memory_fd` extension is enabled.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn import_fd(
+        &self,
+        device: &ash::Device,
+        external_memory_fd: &ash::khr::external_memory_fd::Device,
+        fd: i32,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        descriptor: &ImportDescriptor<LT>,
+    ) -> Result<Allocation<LT>> {
+        let memory_type_bits = if handle_type == vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD {
+            descriptor.requirements.memory_type_bits
+        } else {
+            let mut properties = vk::MemoryFdPropertiesKHR::default();
+            external_memory_fd
+                .get_memory_fd_properties(handle_type, fd, &mut properties)
+                .map_err(|_| AllocatorError::FailedToImport)?;
+            descriptor.requirements.memory_type_bits & properties.memory_type_bits
+        };
+
+        self.import(
+            device,
+            memory_type_bits,
+            descriptor,
+            &MemoryImport::Fd { handle_type, fd },
+        )
+    }
+
+    /// Imports a host pointer (`VK_EXT_external_memory_host`) as a dedicated allocation.
+    ///
+    /// The memory type is chosen from the memory types reported for the host pointer and the
+    /// `memory_type_bits` of the descriptor.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided device is in a valid state, that the
+    /// `VK_EXT_external_memory_host` extension is enabled and that the host pointer and size are
+    /// aligned to `minImportedHostPointerAlignment`. The host memory needs to stay valid until the
+    /// allocation is deallocated.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn import_host_pointer(
+        &self,
+        device: &ash::Device,
+        external_memory_host: &ash::ext::external_memory_host::Device,
+        host_pointer: *mut c_void,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        descriptor: &ImportDescriptor<LT>,
+    ) -> Result<Allocation<LT>> {
+        let mut properties = vk::MemoryHostPointerPropertiesEXT::default();
+        (external_memory_host
+            .fp()
+            .get_memory_host_pointer_properties_ext)(
+            device.handle(),
+            handle_type,
+            host_pointer,
+            &mut properties,
+        )
+        .result()
+        .map_err(|_| AllocatorError::FailedToImport)?;
+
+        self.import(
+            device,
+            descriptor.requirements.memory_type_bits & properties.memory_type_bits,
+            descriptor,
+            &MemoryImport::HostPointer {
+                handle_type,
+                host_pointer,
+            },
+        )
+    }
+
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    unsafe fn import(
+        &self,
+        device: &ash::Device,
+        memory_type_bits: u32,
+        descriptor: &ImportDescriptor<LT>,
+        import: &MemoryImport,
+    ) -> Result<Allocation<LT>> {
+        let size = descriptor.requirements.size;
+        if size == 0 {
+            return Err(AllocatorError::ZeroSize);
+        }
+
+        let memory_type_index =
+            self.find_memory_type_index(descriptor.location, memory_type_bits)?;
+
+        #[cfg(feature = "tracing")]
+        debug!(
+            "Importing {} bytes of external memory on memory type {}",
+            size, memory_type_index
+        );
+
+        let lifetime_pools = self.lifetime_pools(descriptor.lifetime, descriptor.device_mask)?;
+        let pool = get_pool(&lifetime_pools, descriptor.lifetime, memory_type_index)?;
+        let pool_hook = self.memory_hooks.read().get(&descriptor.lifetime).cloned();
+
+        let mut pool = pool.lock();
+        let allocation = pool.allocate_dedicated(
+            device,
+            size,
+            Some(import),
+            pool_hook.as_ref(),
+            AllocationLabel {
+                id: self.next_allocation_id(),
+                name: descriptor.name.clone(),
+                tag: descriptor.tag,
+                device_address: None,
+                #[cfg(feature = "backtrace")]
+                backtrace: Some(Arc::new(Backtrace::force_capture())),
+            },
+        );
+        self.validate_after_operation(&pool);
+
+        allocation
+    }
+
+    /// Hands out the id of a new allocation.
+    #[inline]
+    fn next_allocation_id(&self) -> u64 {
+        self.next_allocation_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Sets the memory hook that is used for the memory blocks of all pools of the lifetime.
+    /// Only memory blocks created after setting the hook are affected.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn set_memory_hook(&self, lifetime: LT, hook: Option<Arc<dyn MemoryHook>>) {
+        let mut memory_hooks = self.memory_hooks.write();
+        match hook {
+            Some(hook) => memory_hooks.insert(lifetime, hook),
+            None => memory_hooks.remove(&lifetime),
+        };
+    }
+
+    /// The host allocation callbacks the allocator passes to Vulkan. Helpers that create Vulkan
+    /// objects for allocations should pass them too.
+    #[inline]
+    pub fn allocation_callbacks(&self) -> Option<&vk::AllocationCallbacks<'static>> {
+        self.settings.allocation.as_ref()
+    }
+
+    /// Returns the pools of the lifetime. Creates the pools if they don't exist yet.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn lifetime_pools(
+        &self,
+        lifetime: LT,
+        device_mask: u32,
+    ) -> Result<MappedRwLockReadGuard<'_, LifetimePools<LT>>> {
+        let key = (lifetime, device_mask);
+        let has_key = self.pools.read().contains_key(&key);
+        if !has_key {
+            // The pools are created under the write lock, so they can't miss an epoch change of
+            // a concurrent cleanup.
+            let mut all_pools = self.pools.write();
+            if let Entry::Vacant(entry) = all_pools.entry(key) {
+                let epoch = self.epoch.load(Ordering::Relaxed);
+                let mut pools = Vec::with_capacity(self.memory_types.len());
+                for (i, memory_type) in self.memory_types.iter().enumerate() {
+                    let pool = MemoryPool::new(
+                        lifetime,
+                        device_mask,
+                        i.try_into()?,
+                        memory_type,
+                        self.block_size,
+                        self.buffer_image_granularity,
+                        self.settings.clone(),
+                        epoch,
+                    )?;
+                    pools.push(Mutex::new(pool));
+                }
+                entry.insert(pools);
+            }
         }
+
+        RwLockReadGuard::try_map(self.pools.read(), |pools| pools.get(&key)).map_err(|_| {
+            AllocatorError::Internal(format!(
+                "can't find pool for lifetime {:?} and device mask {:#x}",
+                lifetime, device_mask
+            ))
+        })
     }
 
     #[cfg_attr(feature = "profiling", profiling::function)]
@@ -383,7 +1171,9 @@ impl<LT: Lifetime> Allocator<LT> {
         Ok(index)
     }
 
-    /// Frees the allocation.
+    /// Frees the allocation. If debug margins are enabled and the margins of the allocation were
+    /// overwritten, the allocation is still freed and an `AllocatorError::MemoryCorruption` error
+    /// is returned.
     ///
     /// # Safety
     /// Caller needs to make sure that the allocation is not in use anymore and will not be used
@@ -394,25 +1184,10 @@ impl<LT: Lifetime> Allocator<LT> {
         device: &ash::Device,
         allocation: &Allocation<LT>,
     ) -> Result<()> {
-        let memory_type_index: usize = allocation.memory_type_index.try_into()?;
         let pools = &self.pools.read();
-        let memory_pool = &pools
-            .get(&allocation.lifetime)
-            .ok_or_else(|| {
-                AllocatorError::Internal(format!(
-                    "can't find pool for lifetime {:?}",
-                    allocation.lifetime
-                ))
-            })?
-            .get(memory_type_index)
-            .ok_or_else(|| {
-                AllocatorError::Internal(format!(
-                    "can't find memory_type {} in pool {:?}",
-                    memory_type_index, allocation.lifetime
-                ))
-            })?;
+        let mut memory_pool = get_allocation_pool(pools, allocation)?.lock();
 
-        if let Some(chunk_key) = allocation.chunk_key {
+        let result = if let Some(chunk_key) = allocation.chunk_key {
             #[cfg(feature = "tracing")]
             debug!(
                 "Deallocating chunk on device memory 0x{:02x}, offset {}, size {}",
@@ -420,7 +1195,7 @@ impl<LT: Lifetime> Allocator<LT> {
                 allocation.offset,
                 allocation.size
             );
-            memory_pool.lock().free_chunk(chunk_key)?;
+            memory_pool.free_chunk(chunk_key)
         } else {
             // Dedicated block
             #[cfg(feature = "tracing")]
@@ -429,62 +1204,181 @@ impl<LT: Lifetime> Allocator<LT> {
                 allocation.device_memory.as_raw(),
                 allocation.size
             );
-            memory_pool
-                .lock()
-                .free_block(device, allocation.block_key)?;
-        }
+            memory_pool.free_block(device, allocation.block_key)
+        };
+        self.validate_after_operation(&memory_pool);
 
-        Ok(())
+        result
     }
 
-    /// Releases all memory blocks back to the system. Should be called before drop.
-    ///
-    /// # Safety
-    /// Caller needs to make sure that no allocations are used anymore and will not being used
-    /// after calling this function.
+    /// Checks the internal consistency of all pools: Every free chunk is in exactly one and the
+    /// correct free list, the links between chunks are symmetric, the chunks of a block don't
+    /// overlap and cover the whole block and no adjacent free chunks are left unmerged. Returns
+    /// an `AllocatorError::InvalidState` error that lists all problems if the state is invalid.
     #[cfg_attr(feature = "profiling", profiling::function)]
-    pub unsafe fn cleanup(&self, device: &ash::Device) {
-        for (_, mut lifetime_pools) in self.pools.write().drain() {
-            lifetime_pools.drain(..).for_each(|pool| {
-                pool.lock().blocks.iter_mut().for_each(|block| {
-                    if let Some(block) = block {
-                        block.destroy(device)
-                    }
-                })
-            });
-        }
-    }
-
-    /// Number of allocations.
-    #[cfg_attr(feature = "profiling", profiling::function)]
-    pub fn allocation_count(&self) -> usize {
-        let mut count = 0;
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
         for (_, lifetime_pools) in self.pools.read().iter() {
-            lifetime_pools.iter().for_each(|pool| {
-                let pool = pool.lock();
-                for chunk in pool.chunks.iter().flatten() {
-                    if chunk.chunk_type != ChunkType::Free {
-                        count += 1;
-                    }
+            for pool in lifetime_pools.iter() {
+                if let Err(err) = pool.lock().validate() {
+                    problems.push(err.to_string());
                 }
-            });
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(AllocatorError::InvalidState(problems.join("\n")))
+        }
+    }
+
+    /// Validates the pool after an operation if `validate_operations` is set in debug builds.
+    #[inline]
+    #[allow(unused_variables)]
+    fn validate_after_operation(&self, pool: &MemoryPool<LT>) {
+        #[cfg(debug_assertions)]
+        if self.validate_operations {
+            let result = pool.validate();
+            debug_assert!(result.is_ok(), "{:?}", result);
+        }
+    }
+
+    /// Returns an `AllocatorError::StaleAllocation` error if the allocation was already freed.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn check_allocation(&self, allocation: &Allocation<LT>) -> Result<()> {
+        let pools = &self.pools.read();
+        let memory_pool = get_allocation_pool(pools, allocation)?.lock();
+
+        if let Some(chunk_key) = allocation.chunk_key {
+            memory_pool.check_chunk_key(chunk_key)?;
+        } else {
+            memory_pool.check_dedicated_block_key(allocation.block_key)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a valid mapped slice if the memory is host visible, otherwise it will return None.
+    /// Unlike `Allocation::mapped_slice`, it returns an `AllocatorError::StaleAllocation` error
+    /// if the allocation was already freed.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the allocation is coherent.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn mapped_slice<'a>(
+        &self,
+        allocation: &'a Allocation<LT>,
+    ) -> Result<Option<&'a [u8]>> {
+        self.check_allocation(allocation)?;
+        allocation.mapped_slice()
+    }
+
+    /// Returns a valid mapped mutable slice if the memory is host visible, otherwise it will
+    /// return None. Unlike `Allocation::mapped_slice_mut`, it returns an
+    /// `AllocatorError::StaleAllocation` error if the allocation was already freed.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the allocation is coherent.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn mapped_slice_mut<'a>(
+        &self,
+        allocation: &'a mut Allocation<LT>,
+    ) -> Result<Option<&'a mut [u8]>> {
+        self.check_allocation(allocation)?;
+        allocation.mapped_slice_mut()
+    }
+
+    /// Releases all memory blocks back to the system. Should be called before drop, unless the
+    /// allocator is managed. Returns the allocations that were still alive and got freed with
+    /// their memory blocks.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that no allocations are used anymore and will not being used
+    /// after calling this function.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn cleanup(&self, device: &ash::Device) -> Vec<AllocationInfo<LT>> {
+        // The leaks are collected under the same lock as the drain, so no allocation can be
+        // created or freed in between.
+        let mut pools = self.pools.write();
+        let leaks = collect_leaks(&pools);
+        // The pools are recreated with fresh generations, so the keys of allocations from before
+        // the cleanup are told apart by the epoch.
+        self.epoch.fetch_add(1, Ordering::Relaxed);
+
+        for (_, mut lifetime_pools) in pools.drain() {
+            lifetime_pools
+                .drain(..)
+                .for_each(|pool| pool.lock().destroy_blocks(device));
+        }
+
+        leaks
+    }
+
+    /// Calls the visitor for every live allocation, including dedicated allocations.
+    ///
+    /// The pool of the visited allocations is locked while the visitor runs, so the visitor must
+    /// not call into the allocator.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn for_each_allocation(&self, mut visitor: impl FnMut(&AllocationInfo<LT>)) {
+        for lifetime_pools in self.pools.read().values() {
+            lifetime_pools
+                .iter()
+                .for_each(|pool| pool.lock().visit_allocations(&mut |info| visitor(&info)));
         }
+    }
+
+    /// Finds what occupies the offset inside of a `DeviceMemory`, for example to attribute a
+    /// validation error or a crash report to an allocation. Returns None if the `DeviceMemory`
+    /// isn't owned by the allocator or the offset lies outside of it.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn find_allocation(
+        &self,
+        device_memory: vk::DeviceMemory,
+        offset: vk::DeviceSize,
+    ) -> Option<AllocationLookup<LT>> {
+        self.pools.read().values().find_map(|lifetime_pools| {
+            lifetime_pools
+                .iter()
+                .find_map(|pool| pool.lock().find_allocation(device_memory, offset))
+        })
+    }
+
+    /// Returns all allocations that are still alive. Emits a warning for each of them when the
+    /// `tracing` feature is enabled.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn check_leaks(&self) -> Vec<AllocationInfo<LT>> {
+        collect_leaks(&self.pools.read())
+    }
 
+    /// Verifies the debug margins of all sub allocations in host visible memory and returns the
+    /// allocations whose margins were overwritten. Always returns an empty list if debug margins
+    /// are disabled.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the mapped memory of the allocations is not written to
+    /// while the margins are checked.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn check_corruption(&self) -> Result<Vec<AllocationInfo<LT>>> {
+        let mut corrupted = Vec::new();
         for (_, lifetime_pools) in self.pools.read().iter() {
-            lifetime_pools.iter().for_each(|pool| {
-                let pool = pool.lock();
-                for block in pool.blocks.iter().flatten() {
-                    if block.is_dedicated {
-                        count += 1;
-                    }
-                }
-            });
+            for pool in lifetime_pools.iter() {
+                pool.lock().collect_corrupted_allocations(&mut corrupted)?;
+            }
         }
+        Ok(corrupted)
+    }
 
-        count
+    /// Number of allocations.
+    #[inline]
+    pub fn allocation_count(&self) -> usize {
+        self.settings
+            .counters
+            .allocation_count
+            .load(Ordering::Relaxed)
     }
 
-    /// Number of unused ranges between allocations.
+    /// Number of unused ranges between allocations. Debug margins are not unused.
     #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn unused_range_count(&self) -> usize {
         let mut unused_count: usize = 0;
@@ -500,7 +1394,7 @@ impl<LT: Lifetime> Allocator<LT> {
                         let chunk = pool.chunks[next_key.get()]
                             .as_ref()
                             .expect("can't find chunk in chunk list");
-                        if chunk.offset != previous_offset + previous_size {
+                        if pool.unused_gap(chunk, previous_offset + previous_size) != 0 {
                             unused_count += 1;
                         }
 
@@ -521,36 +1415,26 @@ impl<LT: Lifetime> Allocator<LT> {
     }
 
     /// Number of bytes used by the allocations.
-    #[cfg_attr(feature = "profiling", profiling::function)]
+    #[inline]
     pub fn used_bytes(&self) -> vk::DeviceSize {
-        let mut bytes = 0;
-
-        for (_, lifetime_pools) in self.pools.read().iter() {
-            lifetime_pools.iter().for_each(|pool| {
-                let pool = pool.lock();
-                for chunk in pool.chunks.iter().flatten() {
-                    if chunk.chunk_type != ChunkType::Free {
-                        bytes += chunk.size;
-                    }
-                }
-            });
-        }
-
-        for (_, lifetime_pools) in self.pools.read().iter() {
-            lifetime_pools.iter().for_each(|pool| {
-                let pool = pool.lock();
-                for block in pool.blocks.iter().flatten() {
-                    if block.is_dedicated {
-                        bytes += block.size;
-                    }
-                }
-            });
-        }
+        self.settings
+            .counters
+            .allocation_bytes
+            .load(Ordering::Relaxed)
+    }
 
-        bytes
+    /// The highest number of bytes used by the allocations since the allocator was created or
+    /// the peaks were reset.
+    #[inline]
+    pub fn peak_used_bytes(&self) -> vk::DeviceSize {
+        self.settings
+            .counters
+            .peak_allocation_bytes
+            .load(Ordering::Relaxed)
     }
 
-    /// Number of bytes used by the unused ranges between allocations.
+    /// Number of bytes used by the unused ranges between allocations. Doesn't include the debug
+    /// margins, like `Statistics::free_bytes`.
     #[cfg_attr(feature = "profiling", profiling::function)]
     pub fn unused_bytes(&self) -> vk::DeviceSize {
         let mut unused_bytes: vk::DeviceSize = 0;
@@ -566,9 +1450,7 @@ impl<LT: Lifetime> Allocator<LT> {
                         let chunk = pool.chunks[next_key.get()]
                             .as_ref()
                             .expect("can't find chunk in chunk list");
-                        if chunk.offset != previous_offset + previous_size {
-                            unused_bytes += chunk.offset - (previous_offset + previous_size);
-                        }
+                        unused_bytes += pool.unused_gap(chunk, previous_offset + previous_size);
 
                         if let Some(key) = chunk.next {
                             next_key = key
@@ -586,19 +1468,113 @@ impl<LT: Lifetime> Allocator<LT> {
         unused_bytes
     }
 
-    /// Number of allocated Vulkan memory blocks.
+    /// Returns a snapshot of the statistics of all pools, broken down by memory heap, memory
+    /// type and lifetime. Walks every pool only once.
     #[cfg_attr(feature = "profiling", profiling::function)]
-    pub fn block_count(&self) -> usize {
-        let mut count: usize = 0;
+    pub fn statistics(&self) -> Result<AllocatorStatistics<LT>> {
+        let heap_count: usize = self.memory_properties.memory_heap_count.try_into()?;
+        let mut statistics = AllocatorStatistics {
+            total: Statistics::default(),
+            heaps: vec![Statistics::default(); heap_count],
+            memory_types: vec![Statistics::default(); self.memory_types.len()],
+            lifetimes: HashMap::new(),
+            requests: HashMap::new(),
+        };
 
-        for (_, lifetime_pools) in self.pools.read().iter() {
-            count += lifetime_pools
+        for ((lifetime, _), lifetime_pools) in self.pools.read().iter() {
+            let lifetime_statistics = statistics.lifetimes.entry(*lifetime).or_default();
+            for pool in lifetime_pools.iter() {
+                let pool = pool.lock();
+                let pool_statistics = pool.statistics();
+                let heap_index: usize = pool.heap_index.try_into()?;
+                let memory_type_index: usize = pool.memory_type_index.try_into()?;
+
+                statistics.total.add(&pool_statistics);
+                statistics.heaps[heap_index].add(&pool_statistics);
+                statistics.memory_types[memory_type_index].add(&pool_statistics);
+                lifetime_statistics.add(&pool_statistics);
+
+                if !pool.requests.sizes.is_empty() {
+                    statistics
+                        .requests
+                        .entry((*lifetime, pool.memory_type_index))
+                        .or_default()
+                        .add(&pool.requests);
+                }
+            }
+        }
+
+        Ok(statistics)
+    }
+
+    /// Clears the histograms of the allocation requests.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn reset_request_histograms(&self) {
+        for lifetime_pools in self.pools.read().values() {
+            lifetime_pools
                 .iter()
-                .map(|pool| pool.lock().blocks.len())
-                .sum::<usize>();
+                .for_each(|pool| pool.lock().requests = RequestHistogram::default());
+        }
+    }
+
+    /// Returns the fragmentation of the free chunks of each pool and in total.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn fragmentation(&self) -> AllocatorFragmentation<LT> {
+        let mut fragmentation = AllocatorFragmentation {
+            total: Fragmentation::default(),
+            pools: Vec::new(),
+        };
+
+        for lifetime_pools in self.pools.read().values() {
+            for pool in lifetime_pools.iter() {
+                let pool = pool.lock();
+                if pool.blocks.iter().flatten().next().is_none() {
+                    continue;
+                }
+
+                let pool_fragmentation = pool.fragmentation();
+                fragmentation.total.add(&pool_fragmentation);
+                fragmentation.pools.push(PoolFragmentation {
+                    lifetime: pool.lifetime,
+                    device_mask: pool.device_mask,
+                    memory_type_index: pool.memory_type_index,
+                    fragmentation: pool_fragmentation,
+                });
+            }
         }
 
-        count
+        fragmentation
+    }
+
+    /// Number of live memory blocks, including the blocks of dedicated allocations.
+    ///
+    /// Up to 2.3 this counted the slots of the block lists, which included the slots of freed
+    /// blocks and one reserved slot per memory type pool. Freed blocks are no longer counted.
+    #[inline]
+    pub fn block_count(&self) -> usize {
+        self.settings.counters.block_count.load(Ordering::Relaxed)
+    }
+
+    /// Number of bytes of all memory blocks.
+    #[inline]
+    pub fn block_bytes(&self) -> vk::DeviceSize {
+        self.settings.counters.block_bytes.load(Ordering::Relaxed)
+    }
+
+    /// The highest number of bytes of all memory blocks since the allocator was created or the
+    /// peaks were reset.
+    #[inline]
+    pub fn peak_block_bytes(&self) -> vk::DeviceSize {
+        self.settings
+            .counters
+            .peak_block_bytes
+            .load(Ordering::Relaxed)
+    }
+
+    /// Resets the peak values to the current usage.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn reset_peaks(&self) {
+        self.settings.counters.reset_peaks();
     }
 }
 
@@ -649,6 +1625,65 @@ pub struct AllocationDescriptor<LT: Lifetime> {
     /// True if the allocation is for a optimal image (regular textures). Buffers and linear
     /// images need to set this false.
     pub is_optimal: bool,
+    /// The device mask of the physical devices in a device group the memory is allocated on.
+    /// Allocations with different masks never share a memory block. 0 allocates the memory on
+    /// all physical devices of the device group.
+    pub device_mask: u32,
+    /// A memory hook that is only used for this allocation. Overrides the hook of the lifetime.
+    /// Since sub allocations share their memory block, setting a hook forces a dedicated allocation.
+    pub memory_hook: Option<Arc<dyn MemoryHook>>,
+    /// An optional name of the allocation. Shows up in leak reports and tracing events.
+    pub name: Option<Cow<'static, str>>,
+    /// A user defined tag of the allocation. Shows up in leak reports and tracing events.
+    pub tag: u64,
+}
+
+/// The descriptor of an allocation for a buffer or an image. The memory requirements are queried
+/// from the resource.
+#[derive(Clone, Debug)]
+pub struct ResourceAllocationDescriptor<LT: Lifetime> {
+    /// Location where the memory allocation should be stored.
+    pub location: MemoryLocation,
+    /// The lifetime of the allocation.
+    pub lifetime: LT,
+    /// The device mask of the physical devices in a device group the memory is allocated on.
+    /// 0 allocates the memory on all physical devices of the device group.
+    pub device_mask: u32,
+    /// An optional name of the allocation. Shows up in leak reports and tracing events.
+    pub name: Option<Cow<'static, str>>,
+    /// A user defined tag of the allocation. Shows up in leak reports and tracing events.
+    pub tag: u64,
+}
+
+/// The descriptor for importing external memory into the allocator.
+#[derive(Clone, Debug)]
+pub struct ImportDescriptor<LT: Lifetime> {
+    /// Location where the imported memory should be placed.
+    pub location: MemoryLocation,
+    /// Vulkan memory requirements of the imported memory. The alignment is ignored.
+    pub requirements: vk::MemoryRequirements,
+    /// The lifetime of the allocation.
+    pub lifetime: LT,
+    /// The device mask of the physical devices in a device group the memory is imported on.
+    /// 0 imports the memory on all physical devices of the device group.
+    pub device_mask: u32,
+    /// An optional name of the allocation. Shows up in leak reports and tracing events.
+    pub name: Option<Cow<'static, str>>,
+    /// A user defined tag of the allocation. Shows up in leak reports and tracing events.
+    pub tag: u64,
+}
+
+/// External memory that is imported into a dedicated memory block.
+#[derive(Clone, Copy, Debug)]
+enum MemoryImport {
+    Fd {
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        fd: i32,
+    },
+    HostPointer {
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        host_pointer: *mut c_void,
+    },
 }
 
 /// An allocation of the `Allocator`.
@@ -656,13 +1691,15 @@ pub struct AllocationDescriptor<LT: Lifetime> {
 pub struct Allocation<LT: Lifetime> {
     memory_type_index: u32,
     lifetime: LT,
-    block_key: NonZeroUsize,
-    chunk_key: Option<NonZeroUsize>,
+    device_mask: u32,
+    block_key: SlotKey,
+    chunk_key: Option<SlotKey>,
     mapped_ptr: Option<std::ptr::NonNull<c_void>>,
 
     device_memory: vk::DeviceMemory,
     offset: vk::DeviceSize,
     size: vk::DeviceSize,
+    label: AllocationLabel,
 }
 
 unsafe impl<LT: Lifetime> Send for Allocation<LT> {}
@@ -688,8 +1725,34 @@ impl<LT: Lifetime> Allocation<LT> {
         self.size
     }
 
+    /// The device mask of the physical devices the memory was allocated on. 0 if the memory was
+    /// allocated on all physical devices of the device group.
+    #[inline]
+    pub fn device_mask(&self) -> u32 {
+        self.device_mask
+    }
+
+    /// The id of the allocation. Unique over the lifetime of the allocator.
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.label.id
+    }
+
+    /// The name of the allocation.
+    #[inline]
+    pub fn name(&self) -> Option<&str> {
+        self.label.name.as_deref()
+    }
+
+    /// The user defined tag of the allocation.
+    #[inline]
+    pub fn tag(&self) -> u64 {
+        self.label.tag
+    }
+
     /// Returns a valid mapped slice if the memory is host visible, otherwise it will return None.
     /// The slice already references the exact memory region of the sub allocation, so no offset needs to be applied.
+    /// Use `Allocator::mapped_slice` to detect allocations that were already freed.
     ///
     /// # Safety
     /// Caller needs to make sure that the allocation is still valid and coherent.
@@ -707,6 +1770,7 @@ impl<LT: Lifetime> Allocation<LT> {
 
     /// Returns a valid mapped mutable slice if the memory is host visible, otherwise it will return None.
     /// The slice already references the exact memory region of the sub allocation, so no offset needs to be applied.
+    /// Use `Allocator::mapped_slice_mut` to detect allocations that were already freed.
     ///
     /// # Safety
     /// Caller needs to make sure that the allocation is still valid and coherent.
@@ -723,6 +1787,39 @@ impl<LT: Lifetime> Allocation<LT> {
     }
 }
 
+/// The id, name, tag and device address of an allocation. Free chunks have the id 0.
+#[derive(Clone, Debug, Default)]
+struct AllocationLabel {
+    id: u64,
+    name: Option<Cow<'static, str>>,
+    tag: u64,
+    device_address: Option<vk::DeviceAddress>,
+    #[cfg(feature = "backtrace")]
+    backtrace: Option<Arc<Backtrace>>,
+}
+
+impl PartialEq for AllocationLabel {
+    fn eq(&self, other: &Self) -> bool {
+        // Backtraces can't be compared.
+        self.id == other.id
+            && self.name == other.name
+            && self.tag == other.tag
+            && self.device_address == other.device_address
+    }
+}
+
+impl Eq for AllocationLabel {}
+
+/// A generational key into the block or chunk list of a pool. The generation of a slot changes
+/// every time it's handed out, so keys of allocations that were already freed can be detected.
+/// The epoch of the allocator tells keys apart that were handed out before a cleanup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct SlotKey {
+    index: NonZeroUsize,
+    generation: u32,
+    epoch: u32,
+}
+
 #[derive(Clone, Debug)]
 struct BestFitCandidate {
     aligned_offset: u64,
@@ -736,23 +1833,51 @@ struct BestFitCandidate {
 /// Used to separate buffer (linear) and texture (optimal) memory regions,
 /// so that internal memory fragmentation is kept low.
 #[derive(Debug)]
-struct MemoryPool {
+struct MemoryPool<LT: Lifetime> {
+    lifetime: LT,
+    device_mask: u32,
     memory_type_index: u32,
+    heap_index: u32,
     block_size: vk::DeviceSize,
     is_mappable: bool,
+    buffer_image_granularity: u64,
+    settings: PoolSettings<LT>,
     blocks: Vec<Option<MemoryBlock>>,
     chunks: Vec<Option<MemoryChunk>>,
     free_chunks: Vec<Vec<NonZeroUsize>>,
     max_bucket_index: u32,
 
+    // The current generation of every slot inside the block and chunks lists.
+    block_generations: Vec<u32>,
+    chunk_generations: Vec<u32>,
+    // The epoch of the allocator when the pool was created.
+    epoch: u32,
+
     // Helper lists to find free slots inside the block and chunks lists.
     free_block_slots: Vec<NonZeroUsize>,
     free_chunk_slots: Vec<NonZeroUsize>,
+
+    // Finds the block of a `DeviceMemory`.
+    block_lookup: HashMap<vk::DeviceMemory, NonZeroUsize>,
+
+    // The usage of this pool. The allocator wide counters are updated alongside.
+    counters: UsageCounters,
+    requests: RequestHistogram,
 }
 
-impl MemoryPool {
+impl<LT: Lifetime> MemoryPool<LT> {
+    #[allow(clippy::too_many_arguments)]
     #[cfg_attr(feature = "profiling", profiling::function)]
-    fn new(block_size: vk::DeviceSize, memory_type_index: u32, is_mappable: bool) -> Result<Self> {
+    fn new(
+        lifetime: LT,
+        device_mask: u32,
+        memory_type_index: u32,
+        memory_type: &vk::MemoryType,
+        block_size: vk::DeviceSize,
+        buffer_image_granularity: u64,
+        settings: PoolSettings<LT>,
+        epoch: u32,
+    ) -> Result<Self> {
         let mut blocks = Vec::with_capacity(128);
         let mut chunks = Vec::with_capacity(128);
 
@@ -778,27 +1903,54 @@ impl MemoryPool {
         }
 
         Ok(Self {
+            lifetime,
+            device_mask,
             memory_type_index,
+            heap_index: memory_type.heap_index,
             block_size,
-            is_mappable,
+            is_mappable: memory_type
+                .property_flags
+                .contains(vk::MemoryPropertyFlags::HOST_VISIBLE),
+            buffer_image_granularity,
+            settings,
             blocks,
             chunks,
             free_chunks,
+            block_generations: vec![0],
+            chunk_generations: vec![0],
+            epoch,
             free_block_slots: Vec::with_capacity(16),
             free_chunk_slots: Vec::with_capacity(16),
+            block_lookup: HashMap::new(),
             max_bucket_index: bucket_count - 1,
+            counters: UsageCounters::default(),
+            requests: RequestHistogram::default(),
         })
     }
 
     #[cfg_attr(feature = "profiling", profiling::function)]
-    fn add_block(&mut self, block: MemoryBlock) -> NonZeroUsize {
-        if let Some(key) = self.free_block_slots.pop() {
+    fn add_block(&mut self, block: MemoryBlock) -> SlotKey {
+        self.update_counters(|counters| counters.add_block(block.size));
+        let device_memory = block.device_memory;
+
+        let index = if let Some(key) = self.free_block_slots.pop() {
             self.blocks[key.get()] = Some(block);
             key
         } else {
             let key = self.blocks.len();
             self.blocks.push(Some(block));
+            self.block_generations.push(0);
             NonZeroUsize::new(key).expect("new block key was zero")
+        };
+
+        let generation = &mut self.block_generations[index.get()];
+        *generation = generation.wrapping_add(1);
+        self.block_lookup.insert(device_memory, index);
+
+        SlotKey {
+            index,
+            generation: *generation,
+            epoch: self.epoch,
         }
     }
 
@@ -810,47 +1962,113 @@ impl MemoryPool {
         } else {
             let key = self.chunks.len();
             self.chunks.push(Some(chunk));
+            self.chunk_generations.push(0);
             NonZeroUsize::new(key).expect("new chunk key was zero")
         }
     }
 
+    /// Checks that the key references a dedicated block that was not freed since the key was
+    /// handed out.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn check_dedicated_block_key(&self, key: SlotKey) -> Result<&MemoryBlock> {
+        let block = self
+            .blocks
+            .get(key.index.get())
+            .ok_or(AllocatorError::CantFindBlock)?;
+        match block {
+            Some(block)
+                if block.is_dedicated
+                    && self.block_generations[key.index.get()] == key.generation
+                    && key.epoch == self.epoch =>
+            {
+                Ok(block)
+            }
+            _ => Err(AllocatorError::StaleAllocation),
+        }
+    }
+
+    /// Checks that the key references a chunk that is in use and was not freed since the key was
+    /// handed out.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn check_chunk_key(&self, key: SlotKey) -> Result<&MemoryChunk> {
+        let chunk = self
+            .chunks
+            .get(key.index.get())
+            .ok_or(AllocatorError::CantFindChunk)?;
+        match chunk {
+            Some(chunk)
+                if chunk.chunk_type != ChunkType::Free
+                    && self.chunk_generations[key.index.get()] == key.generation
+                    && key.epoch == self.epoch =>
+            {
+                Ok(chunk)
+            }
+            _ => Err(AllocatorError::StaleAllocation),
+        }
+    }
+
     #[cfg_attr(feature = "profiling", profiling::function)]
-    unsafe fn allocate_dedicated<LT: Lifetime>(
+    unsafe fn allocate_dedicated(
         &mut self,
         device: &ash::Device,
         size: vk::DeviceSize,
-        lifetime: LT,
+        import: Option<&MemoryImport>,
+        hook: Option<&Arc<dyn MemoryHook>>,
+        label: AllocationLabel,
     ) -> Result<Allocation<LT>> {
-        let block = MemoryBlock::new(device, size, self.memory_type_index, self.is_mappable, true)?;
+        let mut block = MemoryBlock::new(
+            device,
+            &MemoryBlockDescriptor {
+                size,
+                lifetime: self.lifetime,
+                memory_type_index: self.memory_type_index,
+                heap_index: self.heap_index,
+                is_mappable: self.is_mappable,
+                is_dedicated: true,
+                device_mask: self.device_mask,
+                import,
+                hook,
+                settings: &self.settings,
+            },
+        )?;
 
+        block.label = label.clone();
         let device_memory = block.device_memory;
         let mapped_ptr = std::ptr::NonNull::new(block.mapped_ptr);
 
         let key = self.add_block(block);
+        self.name_block(key.index, label.name.as_deref());
+        self.update_counters(|counters| counters.add_allocation(size, true));
 
         Ok(Allocation {
             memory_type_index: self.memory_type_index,
-            lifetime,
+            lifetime: self.lifetime,
+            device_mask: self.device_mask,
             block_key: key,
             chunk_key: None,
             device_memory,
             offset: 0,
             size,
             mapped_ptr,
+            label,
         })
     }
 
     #[cfg_attr(feature = "profiling", profiling::function)]
-    unsafe fn allocate<LT: Lifetime>(
+    unsafe fn allocate(
         &mut self,
         device: &ash::Device,
-        buffer_image_granularity: u64,
         size: vk::DeviceSize,
         alignment: vk::DeviceSize,
-        lifetime: LT,
         is_optimal: bool,
+        hook: Option<&Arc<dyn MemoryHook>>,
+        label: AllocationLabel,
     ) -> Result<Allocation<LT>> {
-        let mut bucket_index = calculate_bucket_index(size);
+        let buffer_image_granularity = self.buffer_image_granularity;
+        let margin = self.debug_margin();
+        // The trailing debug margin is part of the chunk, the leading one lies in the padding.
+        let reserved_size = size + margin;
+        let mut bucket_index = calculate_bucket_index(reserved_size);
 
         // Make sure that we don't try to allocate a chunk bigger than the block.
         debug_assert!(bucket_index <= self.max_bucket_index);
@@ -861,10 +2079,19 @@ impl MemoryPool {
             ChunkType::Linear
         };
 
+        let mut allocated_new_block = false;
         loop {
             // We couldn't find a suitable empty chunk, so we will allocate a new block.
             if bucket_index > self.max_bucket_index {
-                self.allocate_new_block(device)?;
+                // A new block would not fit the allocation either.
+                if allocated_new_block {
+                    return Err(AllocatorError::Internal(format!(
+                        "can't fit {} bytes with an alignment of {} into a new memory block",
+                        size, alignment
+                    )));
+                }
+                self.allocate_new_block(device, hook)?;
+                allocated_new_block = true;
                 bucket_index = self.max_bucket_index;
             }
 
@@ -879,11 +2106,11 @@ impl MemoryPool {
                     .expect("can't find chunk in chunk list");
                 debug_assert!(chunk.chunk_type == ChunkType::Free);
 
-                if chunk.size < size {
+                if chunk.size < reserved_size {
                     continue;
                 }
 
-                let mut aligned_offset = 0;
+                let mut aligned_offset = align_up(chunk.offset + margin, alignment);
 
                 // We need to handle the granularity between chunks. See "Buffer-Image Granularity"
                 // in the Vulkan specs.
@@ -899,8 +2126,6 @@ impl MemoryPool {
                             AllocatorError::Internal("previous chunk was empty".into())
                         })?;
 
-                    aligned_offset = align_up(chunk.offset, alignment);
-
                     if previous.chunk_type.granularity_conflict(chunk_type)
                         && is_on_same_page(
                             previous.offset,
@@ -934,7 +2159,7 @@ impl MemoryPool {
                 }
 
                 let padding = aligned_offset - chunk.offset;
-                let aligned_size = padding + size;
+                let aligned_size = padding + reserved_size;
 
                 // Try to find the best fitting chunk.
                 if chunk.size >= aligned_size {
@@ -971,7 +2196,7 @@ impl MemoryPool {
                         .expect("can't find candidate in chunk list")
                         .clone();
 
-                    let new_free_offset = candidate.aligned_offset + size;
+                    let new_free_offset = candidate.aligned_offset + reserved_size;
                     let new_free_size =
                         (candidate_chunk.offset + candidate_chunk.size) - new_free_offset;
 
@@ -982,6 +2207,7 @@ impl MemoryPool {
                         previous: Some(candidate.key),
                         next: candidate_chunk.next,
                         chunk_type: ChunkType::Free,
+                        label: AllocationLabel::default(),
                     };
 
                     let new_free_chunk_key = self.add_chunk(new_free_chunk);
@@ -1000,7 +2226,16 @@ impl MemoryPool {
                     .expect("can't find chunk in chunk list");
                 candidate_chunk.chunk_type = chunk_type;
                 candidate_chunk.offset = candidate.aligned_offset;
-                candidate_chunk.size = size;
+                candidate_chunk.size = reserved_size;
+                candidate_chunk.label = label.clone();
+
+                let generation = &mut self.chunk_generations[candidate.key.get()];
+                *generation = generation.wrapping_add(1);
+                let chunk_key = SlotKey {
+                    index: candidate.key,
+                    generation: *generation,
+                    epoch: self.epoch,
+                };
 
                 let block = self.blocks[candidate_chunk.block_key.get()]
                     .as_ref()
@@ -1016,13 +2251,19 @@ impl MemoryPool {
 
                 let allocation = Allocation {
                     memory_type_index: self.memory_type_index,
-                    lifetime,
-                    block_key: candidate_chunk.block_key,
-                    chunk_key: Some(candidate.key),
+                    lifetime: self.lifetime,
+                    device_mask: self.device_mask,
+                    block_key: SlotKey {
+                        index: candidate_chunk.block_key,
+                        generation: self.block_generations[candidate_chunk.block_key.get()],
+                        epoch: self.epoch,
+                    },
+                    chunk_key: Some(chunk_key),
                     device_memory: block.device_memory,
                     offset: candidate_chunk.offset,
-                    size: candidate_chunk.size,
+                    size,
                     mapped_ptr,
+                    label,
                 };
 
                 // Properly link the chain of chunks.
@@ -1041,6 +2282,14 @@ impl MemoryPool {
                     old_next.previous = new_free_chunk_key;
                 }
 
+                if margin != 0 {
+                    self.write_margins(candidate.key)?;
+                }
+                if let Some(patterns) = self.settings.debug_fill {
+                    self.fill_chunk(candidate.key, patterns.allocate)?;
+                }
+                self.update_counters(|counters| counters.add_allocation(size, false));
+
                 return Ok(allocation);
             }
 
@@ -1049,16 +2298,29 @@ impl MemoryPool {
     }
 
     #[cfg_attr(feature = "profiling", profiling::function)]
-    unsafe fn allocate_new_block(&mut self, device: &ash::Device) -> Result<()> {
+    unsafe fn allocate_new_block(
+        &mut self,
+        device: &ash::Device,
+        hook: Option<&Arc<dyn MemoryHook>>,
+    ) -> Result<()> {
         let block = MemoryBlock::new(
             device,
-            self.block_size,
-            self.memory_type_index,
-            self.is_mappable,
-            false,
+            &MemoryBlockDescriptor {
+                size: self.block_size,
+                lifetime: self.lifetime,
+                memory_type_index: self.memory_type_index,
+                heap_index: self.heap_index,
+                is_mappable: self.is_mappable,
+                is_dedicated: false,
+                device_mask: self.device_mask,
+                import: None,
+                hook,
+                settings: &self.settings,
+            },
         )?;
 
-        let block_key = self.add_block(block);
+        let block_key = self.add_block(block).index;
+        self.name_block(block_key, None);
 
         let chunk = MemoryChunk {
             block_key,
@@ -1067,6 +2329,7 @@ impl MemoryPool {
             previous: None,
             next: None,
             chunk_type: ChunkType::Free,
+            label: AllocationLabel::default(),
         };
 
         let chunk_key = self.add_chunk(chunk);
@@ -1077,45 +2340,257 @@ impl MemoryPool {
         Ok(())
     }
 
+    /// Sets the object name of the `DeviceMemory` of the block if debug utils are enabled. Uses
+    /// the name of the allocation for dedicated blocks if one was set.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    unsafe fn name_block(&self, key: NonZeroUsize, allocation_name: Option<&str>) {
+        let (Some(debug_utils), Some(block)) = (
+            self.settings.debug_utils.as_ref(),
+            self.blocks[key.get()].as_ref(),
+        ) else {
+            return;
+        };
+
+        let name = match allocation_name {
+            Some(name) => name.to_owned(),
+            None => format!(
+                "ash-alloc {:?} type{} block{}",
+                self.lifetime, self.memory_type_index, key
+            ),
+        };
+        // Names with interior nul bytes can't be passed to Vulkan.
+        let Ok(name) = CString::new(name) else {
+            return;
+        };
+
+        let info = vk::DebugUtilsObjectNameInfoEXT::default()
+            .object_handle(block.device_memory)
+            .object_name(&name);
+        #[allow(unused_variables)]
+        if let Err(err) = debug_utils.0.set_debug_utils_object_name(&info) {
+            #[cfg(feature = "tracing")]
+            warn!("Can't set the name of a memory block: {}", err);
+        }
+    }
+
     #[cfg_attr(feature = "profiling", profiling::function)]
-    fn free_chunk(&mut self, chunk_key: NonZeroUsize) -> Result<()> {
+    unsafe fn free_chunk(&mut self, key: SlotKey) -> Result<()> {
+        self.check_chunk_key(key)?;
+        let chunk_key = key.index;
+
+        // The chunk is freed even if it's corrupted, so the memory isn't leaked.
+        let corrupted = if self.margins_intact(chunk_key)? {
+            None
+        } else {
+            self.chunk_info(chunk_key)
+        };
+        if let Some(patterns) = self.settings.debug_fill {
+            self.fill_chunk(chunk_key, patterns.free)?;
+        }
+
         let (previous_key, next_key, size) = {
             let chunk = self.chunks[chunk_key.get()]
                 .as_mut()
                 .ok_or(AllocatorError::CantFindChunk)?;
             chunk.chunk_type = ChunkType::Free;
+            chunk.label = AllocationLabel::default();
             (chunk.previous, chunk.next, chunk.size)
         };
         self.add_to_free_list(chunk_key, size)?;
+        let margin = self.debug_margin();
+        self.update_counters(|counters| counters.remove_allocation(size - margin, false));
 
         self.merge_free_neighbor(next_key, chunk_key, false)?;
         self.merge_free_neighbor(previous_key, chunk_key, true)?;
 
-        Ok(())
-    }
-
-    #[cfg_attr(feature = "profiling", profiling::function)]
-    fn merge_free_neighbor(
-        &mut self,
-        neighbor: Option<NonZeroUsize>,
-        chunk_key: NonZeroUsize,
-        neighbor_is_lhs: bool,
-    ) -> Result<()> {
-        if let Some(neighbor_key) = neighbor {
-            if self.chunks[neighbor_key.get()]
-                .as_ref()
-                .expect("can't find chunk in chunk list")
-                .chunk_type
-                == ChunkType::Free
-            {
-                if neighbor_is_lhs {
-                    self.merge_rhs_into_lhs_chunk(neighbor_key, chunk_key)?;
-                } else {
-                    self.merge_rhs_into_lhs_chunk(chunk_key, neighbor_key)?;
-                }
-            }
+        match corrupted {
+            Some(info) => Err(corruption_error(&info)),
+            None => Ok(()),
         }
-        Ok(())
+    }
+
+    /// The unused bytes between the end of the previous chunk and the chunk. The leading debug
+    /// margin of an allocation lies in front of its chunk, but is not unused.
+    #[inline]
+    fn unused_gap(&self, chunk: &MemoryChunk, previous_end: vk::DeviceSize) -> vk::DeviceSize {
+        let margin = if chunk.chunk_type == ChunkType::Free {
+            0
+        } else {
+            self.debug_margin()
+        };
+        (chunk.offset - previous_end).saturating_sub(margin)
+    }
+
+    /// The size of the debug margins of the pool. Only host visible pools have margins.
+    #[inline]
+    fn debug_margin(&self) -> vk::DeviceSize {
+        if self.is_mappable {
+            self.settings.debug_margin
+        } else {
+            0
+        }
+    }
+
+    /// The space a sub allocation needs at the start of a new block, including the debug margins
+    /// and the padding that aligns the allocation after the leading margin.
+    #[inline]
+    fn reserved_size(&self, size: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
+        let margin = self.debug_margin();
+        if margin == 0 {
+            return size;
+        }
+
+        margin
+            .div_ceil(alignment)
+            .saturating_mul(alignment)
+            .saturating_add(size)
+            .saturating_add(margin)
+    }
+
+    /// Returns the mapped leading and trailing debug margins of a chunk. Returns None if the pool
+    /// has no margins.
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    unsafe fn margins(&self, chunk_key: NonZeroUsize) -> Result<Option<[&mut [u8]; 2]>> {
+        let margin = self.debug_margin();
+        if margin == 0 {
+            return Ok(None);
+        }
+
+        let chunk = self.chunks[chunk_key.get()]
+            .as_ref()
+            .ok_or(AllocatorError::CantFindChunk)?;
+        let block = self.blocks[chunk.block_key.get()]
+            .as_ref()
+            .ok_or(AllocatorError::CantFindBlock)?;
+        if block.mapped_ptr.is_null() {
+            return Ok(None);
+        }
+
+        let length: usize = margin.try_into()?;
+        let leading: usize = (chunk.offset - margin).try_into()?;
+        let trailing: usize = (chunk.offset + chunk.size - margin).try_into()?;
+        let ptr = block.mapped_ptr.cast::<u8>();
+
+        Ok(Some([
+            std::slice::from_raw_parts_mut(ptr.add(leading), length),
+            std::slice::from_raw_parts_mut(ptr.add(trailing), length),
+        ]))
+    }
+
+    /// Fills the mapped memory of a chunk without its debug margin with the pattern.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    unsafe fn fill_chunk(&self, chunk_key: NonZeroUsize, pattern: u8) -> Result<()> {
+        let chunk = self.chunks[chunk_key.get()]
+            .as_ref()
+            .ok_or(AllocatorError::CantFindChunk)?;
+        let block = self.blocks[chunk.block_key.get()]
+            .as_ref()
+            .ok_or(AllocatorError::CantFindBlock)?;
+        if block.mapped_ptr.is_null() {
+            return Ok(());
+        }
+
+        let offset: usize = chunk.offset.try_into()?;
+        let size: usize = (chunk.size - self.debug_margin()).try_into()?;
+        std::ptr::write_bytes(block.mapped_ptr.cast::<u8>().add(offset), pattern, size);
+
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    unsafe fn write_margins(&self, chunk_key: NonZeroUsize) -> Result<()> {
+        if let Some(margins) = self.margins(chunk_key)? {
+            for margin in margins {
+                margin
+                    .iter_mut()
+                    .zip(DEBUG_MARGIN_PATTERN.iter().cycle())
+                    .for_each(|(byte, pattern)| *byte = *pattern);
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    unsafe fn margins_intact(&self, chunk_key: NonZeroUsize) -> Result<bool> {
+        let intact = match self.margins(chunk_key)? {
+            Some(margins) => margins.iter().all(|margin| {
+                margin
+                    .iter()
+                    .zip(DEBUG_MARGIN_PATTERN.iter().cycle())
+                    .all(|(byte, pattern)| byte == pattern)
+            }),
+            None => true,
+        };
+        Ok(intact)
+    }
+
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    unsafe fn collect_corrupted_allocations(
+        &self,
+        allocations: &mut Vec<AllocationInfo<LT>>,
+    ) -> Result<()> {
+        if self.debug_margin() == 0 {
+            return Ok(());
+        }
+
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let (Some(chunk_key), Some(chunk)) = (NonZeroUsize::new(index), chunk) else {
+                continue;
+            };
+            if chunk.chunk_type != ChunkType::Free && !self.margins_intact(chunk_key)? {
+                #[cfg(feature = "tracing")]
+                warn!("Debug margins of chunk {} were overwritten", chunk_key);
+                allocations.extend(self.chunk_info(chunk_key));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the information about the allocation of a chunk.
+    fn chunk_info(&self, chunk_key: NonZeroUsize) -> Option<AllocationInfo<LT>> {
+        let chunk = self.chunks[chunk_key.get()].as_ref()?;
+        let block = self.blocks[chunk.block_key.get()].as_ref()?;
+        Some(AllocationInfo {
+            id: chunk.label.id,
+            lifetime: self.lifetime,
+            memory_type_index: self.memory_type_index,
+            heap_index: self.heap_index,
+            device_memory: block.device_memory,
+            offset: chunk.offset,
+            size: chunk.size - self.debug_margin(),
+            is_dedicated: false,
+            is_mapped: !block.mapped_ptr.is_null(),
+            device_address: chunk.label.device_address,
+            name: chunk.label.name.clone(),
+            tag: chunk.label.tag,
+            #[cfg(feature = "backtrace")]
+            backtrace: chunk.label.backtrace.clone(),
+        })
+    }
+
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn merge_free_neighbor(
+        &mut self,
+        neighbor: Option<NonZeroUsize>,
+        chunk_key: NonZeroUsize,
+        neighbor_is_lhs: bool,
+    ) -> Result<()> {
+        if let Some(neighbor_key) = neighbor {
+            if self.chunks[neighbor_key.get()]
+                .as_ref()
+                .expect("can't find chunk in chunk list")
+                .chunk_type
+                == ChunkType::Free
+            {
+                if neighbor_is_lhs {
+                    self.merge_rhs_into_lhs_chunk(neighbor_key, chunk_key)?;
+                } else {
+                    self.merge_rhs_into_lhs_chunk(chunk_key, neighbor_key)?;
+                }
+            }
+        }
+        Ok(())
     }
 
     #[cfg_attr(feature = "profiling", profiling::function)]
@@ -1178,18 +2653,377 @@ impl MemoryPool {
     }
 
     #[cfg_attr(feature = "profiling", profiling::function)]
-    unsafe fn free_block(&mut self, device: &ash::Device, block_key: NonZeroUsize) -> Result<()> {
-        let mut block = self.blocks[block_key.get()]
+    unsafe fn free_block(&mut self, device: &ash::Device, key: SlotKey) -> Result<()> {
+        self.check_dedicated_block_key(key)?;
+
+        let mut block = self.blocks[key.index.get()]
             .take()
             .ok_or(AllocatorError::CantFindBlock)?;
 
-        block.destroy(device);
+        block.destroy(device, self.lifetime, &self.settings);
+        self.update_counters(|counters| {
+            counters.remove_allocation(block.size, true);
+            counters.remove_block(block.size);
+        });
+        self.block_lookup.remove(&block.device_memory);
 
-        self.free_block_slots.push(block_key);
+        self.free_block_slots.push(key.index);
 
         Ok(())
     }
 
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn collect_allocations(&self, allocations: &mut Vec<AllocationInfo<LT>>) {
+        self.visit_allocations(&mut |info| allocations.push(info));
+    }
+
+    /// Calls the visitor for every non free chunk and every dedicated block.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn visit_allocations(&self, visitor: &mut dyn FnMut(AllocationInfo<LT>)) {
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let (Some(chunk_key), Some(chunk)) = (NonZeroUsize::new(index), chunk) else {
+                continue;
+            };
+            if chunk.chunk_type != ChunkType::Free {
+                if let Some(info) = self.chunk_info(chunk_key) {
+                    visitor(info);
+                }
+            }
+        }
+
+        for block in self.blocks.iter().flatten() {
+            if block.is_dedicated {
+                visitor(self.dedicated_info(block));
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn dedicated_info(&self, block: &MemoryBlock) -> AllocationInfo<LT> {
+        AllocationInfo {
+            id: block.label.id,
+            lifetime: self.lifetime,
+            memory_type_index: self.memory_type_index,
+            heap_index: self.heap_index,
+            device_memory: block.device_memory,
+            offset: 0,
+            size: block.size,
+            is_dedicated: true,
+            is_mapped: !block.mapped_ptr.is_null(),
+            device_address: block.label.device_address,
+            name: block.label.name.clone(),
+            tag: block.label.tag,
+            #[cfg(feature = "backtrace")]
+            backtrace: block.label.backtrace.clone(),
+        }
+    }
+
+    /// Finds what occupies the offset inside the `DeviceMemory`. Returns None if the
+    /// `DeviceMemory` isn't owned by the pool or the offset lies outside of it.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn find_allocation(
+        &self,
+        device_memory: vk::DeviceMemory,
+        offset: vk::DeviceSize,
+    ) -> Option<AllocationLookup<LT>> {
+        let block_key = *self.block_lookup.get(&device_memory)?;
+        let block = self.blocks[block_key.get()].as_ref()?;
+        if offset >= block.size {
+            return None;
+        }
+        if block.is_dedicated {
+            return Some(AllocationLookup::Allocation(self.dedicated_info(block)));
+        }
+
+        let margin = self.debug_margin();
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let (Some(chunk_key), Some(chunk)) = (NonZeroUsize::new(index), chunk) else {
+                continue;
+            };
+            if chunk.block_key != block_key
+                || offset < chunk.offset
+                || offset >= chunk.offset + chunk.size
+            {
+                continue;
+            }
+
+            // The trailing debug margin is part of the chunk, but not of the allocation.
+            return if chunk.chunk_type == ChunkType::Free {
+                Some(AllocationLookup::Free {
+                    offset: chunk.offset,
+                    size: chunk.size,
+                })
+            } else if offset < chunk.offset + chunk.size - margin {
+                self.chunk_info(chunk_key).map(AllocationLookup::Allocation)
+            } else {
+                Some(AllocationLookup::Padding)
+            };
+        }
+
+        Some(AllocationLookup::Padding)
+    }
+
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn statistics(&self) -> Statistics {
+        let counters = &self.counters;
+        let block_bytes = counters.block_bytes.load(Ordering::Relaxed);
+        let allocation_bytes = counters.allocation_bytes.load(Ordering::Relaxed);
+
+        // The largest free range is inside the highest non empty bucket.
+        let largest_free_range = self
+            .free_chunks
+            .iter()
+            .rev()
+            .find(|free_list| !free_list.is_empty())
+            .and_then(|free_list| {
+                free_list
+                    .iter()
+                    .filter_map(|key| self.chunks[key.get()].as_ref())
+                    .map(|chunk| chunk.size)
+                    .max()
+            })
+            .unwrap_or(0);
+
+        // Every sub allocation is surrounded by two debug margins that can't be allocated.
+        let allocation_count = counters.allocation_count.load(Ordering::Relaxed);
+        let dedicated_allocation_count =
+            counters.dedicated_allocation_count.load(Ordering::Relaxed);
+        let margin = self.debug_margin();
+        // A usize always fits into a DeviceSize on the supported platforms.
+        #[allow(clippy::as_conversions)]
+        let margin_bytes =
+            (allocation_count - dedicated_allocation_count) as vk::DeviceSize * margin * 2;
+
+        Statistics {
+            block_count: counters.block_count.load(Ordering::Relaxed),
+            block_bytes,
+            allocation_count,
+            allocation_bytes,
+            dedicated_allocation_count,
+            dedicated_allocation_bytes: counters.dedicated_allocation_bytes.load(Ordering::Relaxed),
+            free_bytes: block_bytes - allocation_bytes - margin_bytes,
+            largest_free_range: largest_free_range.saturating_sub(margin * 2),
+        }
+    }
+
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn fragmentation(&self) -> Fragmentation {
+        let mut fragmentation = Fragmentation {
+            free_chunks_per_bucket: self.free_chunks.iter().map(Vec::len).collect(),
+            ..Default::default()
+        };
+
+        // An allocation in a free chunk needs two debug margins, like in `statistics`.
+        let margin = self.debug_margin();
+        for chunk in self
+            .free_chunks
+            .iter()
+            .flatten()
+            .filter_map(|key| self.chunks[key.get()].as_ref())
+        {
+            let usable_size = chunk.size.saturating_sub(margin * 2);
+            fragmentation.free_bytes += usable_size;
+            fragmentation.largest_free_range = fragmentation.largest_free_range.max(usable_size);
+        }
+
+        fragmentation
+    }
+
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        let mut free_list_counts = vec![0usize; self.chunks.len()];
+        for (bucket_index, free_list) in self.free_chunks.iter().enumerate() {
+            for key in free_list.iter() {
+                match self.chunks.get(key.get()).and_then(Option::as_ref) {
+                    Some(chunk) if chunk.chunk_type == ChunkType::Free => {
+                        let expected: usize = calculate_bucket_index(chunk.size).try_into()?;
+                        if expected != bucket_index {
+                            problems.push(format!(
+                                "free chunk {} of size {} is in bucket {} instead of bucket {}",
+                                key, chunk.size, bucket_index, expected
+                            ));
+                        }
+                    }
+                    Some(_) => problems.push(format!(
+                        "used chunk {} is in the free list of bucket {}",
+                        key, bucket_index
+                    )),
+                    None => problems.push(format!(
+                        "free list of bucket {} references missing chunk {}",
+                        bucket_index, key
+                    )),
+                }
+                if let Some(count) = free_list_counts.get_mut(key.get()) {
+                    *count += 1;
+                }
+            }
+        }
+
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let Some(chunk) = chunk else {
+                continue;
+            };
+
+            if chunk.chunk_type == ChunkType::Free && free_list_counts[index] != 1 {
+                problems.push(format!(
+                    "free chunk {} is in {} free lists",
+                    index, free_list_counts[index]
+                ));
+            }
+
+            match self
+                .blocks
+                .get(chunk.block_key.get())
+                .and_then(Option::as_ref)
+            {
+                Some(block) if !block.is_dedicated => {}
+                _ => problems.push(format!(
+                    "chunk {} references missing block {}",
+                    index, chunk.block_key
+                )),
+            }
+
+            if let Some(next_key) = chunk.next {
+                match self.chunks.get(next_key.get()).and_then(Option::as_ref) {
+                    Some(next) => {
+                        if next.previous.map(NonZeroUsize::get) != Some(index) {
+                            problems.push(format!(
+                                "next chunk {} of chunk {} doesn't link back",
+                                next_key, index
+                            ));
+                        }
+                        if next.block_key != chunk.block_key {
+                            problems.push(format!(
+                                "next chunk {} of chunk {} is in another block",
+                                next_key, index
+                            ));
+                        }
+                        if chunk.chunk_type == ChunkType::Free && next.chunk_type == ChunkType::Free
+                        {
+                            problems.push(format!(
+                                "adjacent free chunks {} and {} are not merged",
+                                index, next_key
+                            ));
+                        }
+                    }
+                    None => problems.push(format!(
+                        "chunk {} links to missing next chunk {}",
+                        index, next_key
+                    )),
+                }
+            }
+
+            if let Some(previous_key) = chunk.previous {
+                match self.chunks.get(previous_key.get()).and_then(Option::as_ref) {
+                    Some(previous) if previous.next.map(NonZeroUsize::get) != Some(index) => {
+                        problems.push(format!(
+                            "previous chunk {} of chunk {} doesn't link back",
+                            previous_key, index
+                        ))
+                    }
+                    Some(_) => {}
+                    None => problems.push(format!(
+                        "chunk {} links to missing previous chunk {}",
+                        index, previous_key
+                    )),
+                }
+            }
+        }
+
+        for (block_index, block) in self.blocks.iter().enumerate() {
+            let Some(block) = block else {
+                continue;
+            };
+            if block.is_dedicated {
+                continue;
+            }
+
+            let start_chunks: Vec<usize> = self
+                .chunks
+                .iter()
+                .enumerate()
+                .filter_map(|(index, chunk)| {
+                    chunk
+                        .as_ref()
+                        .filter(|chunk| {
+                            chunk.block_key.get() == block_index && chunk.previous.is_none()
+                        })
+                        .map(|_| index)
+                })
+                .collect();
+            let [start_chunk] = start_chunks[..] else {
+                problems.push(format!(
+                    "block {} has {} start chunks",
+                    block_index,
+                    start_chunks.len()
+                ));
+                continue;
+            };
+
+            // Chunks can be preceded by unused padding, but must never overlap.
+            let mut end = 0;
+            let mut visited = 0;
+            let mut next_key = Some(start_chunk);
+            while let Some(key) = next_key {
+                let Some(chunk) = self.chunks.get(key).and_then(Option::as_ref) else {
+                    break;
+                };
+                if chunk.offset < end {
+                    problems.push(format!(
+                        "chunk {} at offset {} overlaps the previous chunk ending at {}",
+                        key, chunk.offset, end
+                    ));
+                }
+                end = chunk.offset + chunk.size;
+
+                visited += 1;
+                if visited > self.chunks.len() {
+                    problems.push(format!("chunks of block {} form a cycle", block_index));
+                    break;
+                }
+                next_key = chunk.next.map(NonZeroUsize::get);
+            }
+
+            if end != block.size {
+                problems.push(format!(
+                    "chunks of block {} end at {} instead of {}",
+                    block_index, end, block.size
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(AllocatorError::InvalidState(format!(
+                "pool of lifetime {:?} on memory type {}: {}",
+                self.lifetime,
+                self.memory_type_index,
+                problems.join(", ")
+            )))
+        }
+    }
+
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    unsafe fn destroy_blocks(&mut self, device: &ash::Device) {
+        for block in self.blocks.iter_mut().flatten() {
+            block.destroy(device, self.lifetime, &self.settings);
+        }
+        self.block_lookup.clear();
+
+        self.settings.counters.subtract(&self.counters);
+        self.counters = UsageCounters::default();
+    }
+
+    /// Applies a change of the usage to the counters of the pool and the allocator.
+    #[inline]
+    fn update_counters(&self, update: impl Fn(&UsageCounters)) {
+        update(&self.counters);
+        update(&self.settings.counters);
+    }
+
     #[cfg_attr(feature = "profiling", profiling::function)]
     fn add_to_free_list(&mut self, chunk_key: NonZeroUsize, size: vk::DeviceSize) -> Result<()> {
         let chunk_bucket_index: usize = calculate_bucket_index(size).try_into()?;
@@ -1224,6 +3058,133 @@ struct MemoryChunk {
     previous: Option<NonZeroUsize>,
     next: Option<NonZeroUsize>,
     chunk_type: ChunkType,
+    label: AllocationLabel,
+}
+
+/// Settings of the allocator that are shared by all memory pools.
+#[derive(Clone, Debug)]
+struct PoolSettings<LT: Lifetime> {
+    device_memory: DeviceMemoryCallbacks<LT>,
+    allocation: Option<vk::AllocationCallbacks<'static>>,
+    debug_utils: Option<DebugUtils>,
+    debug_margin: vk::DeviceSize,
+    debug_fill: Option<DebugFillPatterns>,
+    counters: Arc<UsageCounters>,
+}
+
+/// Running counters of the memory usage. Allocation sizes don't include the debug margins.
+#[derive(Debug, Default)]
+struct UsageCounters {
+    allocation_count: AtomicUsize,
+    allocation_bytes: AtomicU64,
+    dedicated_allocation_count: AtomicUsize,
+    dedicated_allocation_bytes: AtomicU64,
+    block_count: AtomicUsize,
+    block_bytes: AtomicU64,
+    // The peaks are only reported and reset for the allocator wide counters.
+    peak_allocation_bytes: AtomicU64,
+    peak_block_bytes: AtomicU64,
+}
+
+impl UsageCounters {
+    #[inline]
+    fn add_allocation(&self, size: vk::DeviceSize, is_dedicated: bool) {
+        self.allocation_count.fetch_add(1, Ordering::Relaxed);
+        let bytes = self.allocation_bytes.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak_allocation_bytes
+            .fetch_max(bytes, Ordering::Relaxed);
+        if is_dedicated {
+            self.dedicated_allocation_count
+                .fetch_add(1, Ordering::Relaxed);
+            self.dedicated_allocation_bytes
+                .fetch_add(size, Ordering::Relaxed);
+        }
+    }
+
+    #[inline]
+    fn remove_allocation(&self, size: vk::DeviceSize, is_dedicated: bool) {
+        self.allocation_count.fetch_sub(1, Ordering::Relaxed);
+        self.allocation_bytes.fetch_sub(size, Ordering::Relaxed);
+        if is_dedicated {
+            self.dedicated_allocation_count
+                .fetch_sub(1, Ordering::Relaxed);
+            self.dedicated_allocation_bytes
+                .fetch_sub(size, Ordering::Relaxed);
+        }
+    }
+
+    #[inline]
+    fn add_block(&self, size: vk::DeviceSize) {
+        self.block_count.fetch_add(1, Ordering::Relaxed);
+        let bytes = self.block_bytes.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak_block_bytes.fetch_max(bytes, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn remove_block(&self, size: vk::DeviceSize) {
+        self.block_count.fetch_sub(1, Ordering::Relaxed);
+        self.block_bytes.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    /// Removes the usage of other counters, for example of a destroyed pool.
+    fn subtract(&self, other: &UsageCounters) {
+        let pairs = [
+            (&self.allocation_bytes, &other.allocation_bytes),
+            (
+                &self.dedicated_allocation_bytes,
+                &other.dedicated_allocation_bytes,
+            ),
+            (&self.block_bytes, &other.block_bytes),
+        ];
+        for (counter, other) in pairs {
+            counter.fetch_sub(other.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+
+        let pairs = [
+            (&self.allocation_count, &other.allocation_count),
+            (
+                &self.dedicated_allocation_count,
+                &other.dedicated_allocation_count,
+            ),
+            (&self.block_count, &other.block_count),
+        ];
+        for (counter, other) in pairs {
+            counter.fetch_sub(other.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+    }
+
+    fn reset_peaks(&self) {
+        self.peak_allocation_bytes.store(
+            self.allocation_bytes.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        self.peak_block_bytes
+            .store(self.block_bytes.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+}
+
+/// The `VK_EXT_debug_utils` device functions used to name memory blocks.
+#[derive(Clone)]
+struct DebugUtils(ash::ext::debug_utils::Device);
+
+impl Debug for DebugUtils {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("DebugUtils").field(&self.0.device()).finish()
+    }
+}
+
+/// Describes a memory block that should be created.
+struct MemoryBlockDescriptor<'a, LT: Lifetime> {
+    size: vk::DeviceSize,
+    lifetime: LT,
+    memory_type_index: u32,
+    heap_index: u32,
+    is_mappable: bool,
+    is_dedicated: bool,
+    device_mask: u32,
+    import: Option<&'a MemoryImport>,
+    hook: Option<&'a Arc<dyn MemoryHook>>,
+    settings: &'a PoolSettings<LT>,
 }
 
 /// A reserved memory block.
@@ -1233,45 +3194,98 @@ struct MemoryBlock {
     size: vk::DeviceSize,
     mapped_ptr: *mut c_void,
     is_dedicated: bool,
+    memory_type_index: u32,
+    heap_index: u32,
+    device_mask: u32,
+    hook: Option<Arc<dyn MemoryHook>>,
+    label: AllocationLabel,
 }
 
 unsafe impl Send for MemoryBlock {}
 
 impl MemoryBlock {
     #[cfg_attr(feature = "profiling", profiling::function)]
-    unsafe fn new(
+    unsafe fn new<LT: Lifetime>(
         device: &ash::Device,
-        size: vk::DeviceSize,
-        memory_type_index: u32,
-        is_mappable: bool,
-        is_dedicated: bool,
+        descriptor: &MemoryBlockDescriptor<LT>,
     ) -> Result<Self> {
+        let MemoryBlockDescriptor {
+            size,
+            lifetime,
+            memory_type_index,
+            heap_index,
+            is_mappable,
+            is_dedicated,
+            device_mask,
+            import,
+            hook,
+            settings,
+        } = *descriptor;
+
+        let mut alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(size)
+            .memory_type_index(memory_type_index);
+
         #[cfg(feature = "vk-buffer-device-address")]
-        let device_memory = {
-            let alloc_info = vk::MemoryAllocateInfo::default()
-                .allocation_size(size)
-                .memory_type_index(memory_type_index);
-
-            let allocation_flags = vk::MemoryAllocateFlags::DEVICE_ADDRESS;
-            let mut flags_info = vk::MemoryAllocateFlagsInfo::default().flags(allocation_flags);
-            let alloc_info = alloc_info.push_next(&mut flags_info);
-
-            device
-                .allocate_memory(&alloc_info, None)
-                .map_err(|_| AllocatorError::OutOfMemory)?
+        let mut allocation_flags = vk::MemoryAllocateFlags::DEVICE_ADDRESS;
+        #[cfg(not(feature = "vk-buffer-device-address"))]
+        let mut allocation_flags = vk::MemoryAllocateFlags::empty();
+
+        if device_mask != 0 {
+            allocation_flags |= vk::MemoryAllocateFlags::DEVICE_MASK;
+        }
+
+        let mut flags_info = vk::MemoryAllocateFlagsInfo::default()
+            .flags(allocation_flags)
+            .device_mask(device_mask);
+        if !allocation_flags.is_empty() {
+            alloc_info = alloc_info.push_next(&mut flags_info);
+        }
+
+        let mut import_fd_info;
+        let mut import_host_pointer_info;
+        match import {
+            Some(MemoryImport::Fd { handle_type, fd }) => {
+                import_fd_info = vk::ImportMemoryFdInfoKHR::default()
+                    .handle_type(*handle_type)
+                    .fd(*fd);
+                alloc_info = alloc_info.push_next(&mut import_fd_info);
+            }
+            Some(MemoryImport::HostPointer {
+                handle_type,
+                host_pointer,
+            }) => {
+                import_host_pointer_info = vk::ImportMemoryHostPointerInfoEXT::default()
+                    .handle_type(*handle_type)
+                    .host_pointer(*host_pointer);
+                alloc_info = alloc_info.push_next(&mut import_host_pointer_info);
+            }
+            None => {}
+        }
+
+        let block_info = MemoryBlockInfo {
+            memory_type_index,
+            size,
+            is_dedicated,
+            device_mask,
         };
 
-        #[cfg(not(feature = "vk-buffer-device-address"))]
-        let device_memory = {
-            let alloc_info = vk::MemoryAllocateInfo::default()
-                .allocation_size(size)
-                .memory_type_index(memory_type_index);
-
-            device
-                .allocate_memory(&alloc_info, None)
-                .map_err(|_| AllocatorError::OutOfMemory)?
+        let mut allocate = |info: &vk::MemoryAllocateInfo<'_>| {
+            device.allocate_memory(info, settings.allocation.as_ref())
+        };
+        let device_memory = match hook {
+            Some(hook) => hook.allocate_memory(alloc_info, &block_info, &mut allocate),
+            None => allocate(&alloc_info),
         };
 
+        let device_memory = device_memory.map_err(|_| {
+            if import.is_some() {
+                AllocatorError::FailedToImport
+            } else {
+                AllocatorError::OutOfMemory
+            }
+        })?;
+
         let mapped_ptr = if is_mappable {
             let mapped_ptr = device.map_memory(
                 device_memory,
@@ -1283,7 +3297,7 @@ impl MemoryBlock {
             match mapped_ptr.ok() {
                 Some(mapped_ptr) => mapped_ptr,
                 None => {
-                    device.free_memory(device_memory, None);
+                    device.free_memory(device_memory, settings.allocation.as_ref());
                     return Err(AllocatorError::FailedToMap);
                 }
             }
@@ -1291,24 +3305,84 @@ impl MemoryBlock {
             ptr::null_mut()
         };
 
-        Ok(Self {
+        if let Some(hook) = hook {
+            hook.after_allocate(device_memory, &block_info);
+        }
+
+        let block = Self {
             device_memory,
             size,
             mapped_ptr,
             is_dedicated,
-        })
+            memory_type_index,
+            heap_index,
+            device_mask,
+            hook: hook.cloned(),
+            label: AllocationLabel::default(),
+        };
+
+        if let Some(callback) = &settings.device_memory.allocate {
+            callback(&block.device_memory_info(lifetime));
+        }
+
+        Ok(block)
+    }
+
+    #[inline]
+    fn device_memory_info<LT: Lifetime>(&self, lifetime: LT) -> DeviceMemoryInfo<LT> {
+        DeviceMemoryInfo {
+            device_memory: self.device_memory,
+            memory_type_index: self.memory_type_index,
+            heap_index: self.heap_index,
+            size: self.size,
+            is_dedicated: self.is_dedicated,
+            lifetime,
+        }
+    }
+
+    #[inline]
+    fn info(&self) -> MemoryBlockInfo {
+        MemoryBlockInfo {
+            memory_type_index: self.memory_type_index,
+            size: self.size,
+            is_dedicated: self.is_dedicated,
+            device_mask: self.device_mask,
+        }
     }
 
     #[cfg_attr(feature = "profiling", profiling::function)]
-    unsafe fn destroy(&mut self, device: &ash::Device) {
+    unsafe fn destroy<LT: Lifetime>(
+        &mut self,
+        device: &ash::Device,
+        lifetime: LT,
+        settings: &PoolSettings<LT>,
+    ) {
+        if let Some(callback) = &settings.device_memory.free {
+            callback(&self.device_memory_info(lifetime));
+        }
+        if let Some(hook) = &self.hook {
+            hook.before_free(self.device_memory, &self.info());
+        }
         if !self.mapped_ptr.is_null() {
             device.unmap_memory(self.device_memory);
         }
-        device.free_memory(self.device_memory, None);
+        device.free_memory(self.device_memory, settings.allocation.as_ref());
         self.device_memory = vk::DeviceMemory::null()
     }
 }
 
+/// Creates the error for an allocation whose debug margins were overwritten.
+fn corruption_error<LT: Lifetime>(info: &AllocationInfo<LT>) -> AllocatorError {
+    AllocatorError::MemoryCorruption(format!(
+        "debug margins of allocation {:?} (tag {}) on memory type {} at offset {} with size {} were overwritten",
+        info.name.as_deref().unwrap_or("<unnamed>"),
+        info.tag,
+        info.memory_type_index,
+        info.offset,
+        info.size
+    ))
+}
+
 #[inline]
 fn align_up(offset: vk::DeviceSize, alignment: vk::DeviceSize) -> vk::DeviceSize {
     (offset + (alignment - 1)) & !(alignment - 1)
@@ -1394,8 +3468,97 @@ fn calculate_bucket_index(size: vk::DeviceSize) -> u32 {
     }
 }
 
+/// Counts a value in its bucket. Adds buckets as needed.
+#[inline]
+fn increment_bucket(buckets: &mut Vec<usize>, value: vk::DeviceSize) -> Result<()> {
+    let index: usize = calculate_bucket_index(value).try_into()?;
+    if buckets.len() <= index {
+        buckets.resize(index + 1, 0);
+    }
+    buckets[index] += 1;
+    Ok(())
+}
+
+/// Adds the counts of other buckets. Adds buckets as needed.
+fn add_buckets(buckets: &mut Vec<usize>, other: &[usize]) {
+    if buckets.len() < other.len() {
+        buckets.resize(other.len(), 0);
+    }
+    for (count, other) in buckets.iter_mut().zip(other.iter()) {
+        *count += other;
+    }
+}
+
+#[inline]
+fn get_pool<LT: Lifetime>(
+    lifetime_pools: &[Mutex<MemoryPool<LT>>],
+    lifetime: LT,
+    memory_type_index: usize,
+) -> Result<&Mutex<MemoryPool<LT>>> {
+    lifetime_pools.get(memory_type_index).ok_or_else(|| {
+        AllocatorError::Internal(format!(
+            "can't find memory_type {} in pool {:?}",
+            memory_type_index, lifetime
+        ))
+    })
+}
+
+#[inline]
+fn get_allocation_pool<'a, LT: Lifetime>(
+    pools: &'a HashMap<PoolKey<LT>, LifetimePools<LT>>,
+    allocation: &Allocation<LT>,
+) -> Result<&'a Mutex<MemoryPool<LT>>> {
+    let lifetime_pools = pools
+        .get(&(allocation.lifetime, allocation.device_mask))
+        .ok_or_else(|| {
+            AllocatorError::Internal(format!(
+                "can't find pool for lifetime {:?} and device mask {:#x}",
+                allocation.lifetime, allocation.device_mask
+            ))
+        })?;
+    get_pool(
+        lifetime_pools,
+        allocation.lifetime,
+        allocation.memory_type_index.try_into()?,
+    )
+}
+
+/// Collects all allocations that are still alive. Emits a warning for each of them when the
+/// `tracing` feature is enabled.
+#[cfg_attr(feature = "profiling", profiling::function)]
+fn collect_leaks<LT: Lifetime>(
+    pools: &HashMap<PoolKey<LT>, LifetimePools<LT>>,
+) -> Vec<AllocationInfo<LT>> {
+    let mut leaks = Vec::new();
+    for lifetime_pools in pools.values() {
+        lifetime_pools
+            .iter()
+            .for_each(|pool| pool.lock().collect_allocations(&mut leaks));
+    }
+
+    #[cfg(feature = "tracing")]
+    for leak in leaks.iter() {
+        warn!(
+            "Leaked allocation {:?} (tag {}): lifetime {:?}, memory type {}, offset {}, size {}, dedicated {}",
+            leak.name.as_deref().unwrap_or("<unnamed>"),
+            leak.tag,
+            leak.lifetime,
+            leak.memory_type_index,
+            leak.offset,
+            leak.size,
+            leak.is_dedicated
+        );
+        #[cfg(feature = "backtrace")]
+        if let Some(backtrace) = &leak.backtrace {
+            warn!("Leaked allocation was allocated at:\n{}", backtrace);
+        }
+    }
+
+    leaks
+}
+
 #[inline]
-fn collect_start_chunks(pool: &Mutex<MemoryPool>) -> Vec<NonZeroUsize> {
+fn collect_start_chunks<LT: Lifetime>(pool: &Mutex<MemoryPool<LT>>) -> Vec<NonZeroUsize> {
     pool.lock()
         .chunks
         .iter()
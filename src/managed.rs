@@ -0,0 +1,362 @@
+//! Allocators that own their device.
+
+use std::ffi::c_void;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use crate::{
+    Allocation, AllocationDescriptor, AllocationInfo, Allocator, AllocatorDescriptor,
+    AllocatorError, ImportDescriptor, LeakCallback, Lifetime, MemoryLocation, OwnedAllocation,
+    ResourceAllocationDescriptor, Result,
+};
+use ash::vk;
+
+/// The device and instance a managed allocator was created with.
+pub(crate) struct DeviceHandles<LT: Lifetime> {
+    pub(crate) device: ash::Device,
+    pub(crate) instance: ash::Instance,
+    pub(crate) leak_callback: Option<LeakCallback<LT>>,
+}
+
+impl<LT: Lifetime> Debug for DeviceHandles<LT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DeviceHandles")
+            .field("device", &self.device.handle())
+            .field("instance", &self.instance.handle())
+            .field("leak_callback", &self.leak_callback.is_some())
+            .finish()
+    }
+}
+
+impl<LT: Lifetime> Allocator<LT> {
+    /// Creates a new managed allocator. A managed allocator stores a clone of the device and
+    /// instance, provides the `*_managed` functions that don't need a device and frees all
+    /// remaining memory blocks when dropped.
+    ///
+    /// Every function that takes a device has a `*_managed` variant: the allocation, import, bind,
+    /// deallocation and cleanup functions, including their `*_with_device_mask` and
+    /// `*_with_descriptor` variants and `allocate_owned`. The managed import functions load the extension functions from the
+    /// stored instance and device. All of them return `AllocatorError::NotManaged` if the
+    /// allocator isn't managed.
+    ///
+    /// Leaked allocations are reported when the allocator is dropped, as warnings with the
+    /// `tracing` feature and to `AllocatorDescriptor::leak_callback`. Call
+    /// `Allocator::check_leaks` before dropping the allocator to inspect them.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided instance and device are in a valid state and
+    /// outlive the allocator.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn new_managed(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &ash::Device,
+        descriptor: &AllocatorDescriptor<LT>,
+    ) -> Result<Self> {
+        let mut allocator = Self::new(instance, physical_device, descriptor)?;
+        allocator.handles = Some(DeviceHandles {
+            device: device.clone(),
+            instance: instance.clone(),
+            leak_callback: descriptor.leak_callback.clone(),
+        });
+        Ok(allocator)
+    }
+
+    /// The device of a managed allocator.
+    #[inline]
+    pub fn device(&self) -> Option<&ash::Device> {
+        self.handles.as_ref().map(|handles| &handles.device)
+    }
+
+    /// The instance of a managed allocator.
+    #[inline]
+    pub fn instance(&self) -> Option<&ash::Instance> {
+        self.handles.as_ref().map(|handles| &handles.instance)
+    }
+
+    #[inline]
+    fn managed_device(&self) -> Result<&ash::Device> {
+        self.device().ok_or(AllocatorError::NotManaged)
+    }
+
+    /// Allocates memory for a buffer on the device of a managed allocator.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided buffer is in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_memory_for_buffer_managed(
+        &self,
+        buffer: vk::Buffer,
+        location: MemoryLocation,
+        lifetime: LT,
+    ) -> Result<Allocation<LT>> {
+        self.allocate_memory_for_buffer(self.managed_device()?, buffer, location, lifetime)
+    }
+
+    /// Allocates memory for a buffer on the physical devices of a device group given by the
+    /// device mask, on the device of a managed allocator.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided buffer is in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_memory_for_buffer_with_device_mask_managed(
+        &self,
+        buffer: vk::Buffer,
+        location: MemoryLocation,
+        lifetime: LT,
+        device_mask: u32,
+    ) -> Result<Allocation<LT>> {
+        self.allocate_memory_for_buffer_with_device_mask(
+            self.managed_device()?,
+            buffer,
+            location,
+            lifetime,
+            device_mask,
+        )
+    }
+
+    /// Allocates memory for an image on the device of a managed allocator. `is_optimal` must be
+    /// set true if the image is a optimal image (a regular texture).
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided image is in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_memory_for_image_managed(
+        &self,
+        image: vk::Image,
+        location: MemoryLocation,
+        lifetime: LT,
+        is_optimal: bool,
+    ) -> Result<Allocation<LT>> {
+        self.allocate_memory_for_image(
+            self.managed_device()?,
+            image,
+            location,
+            lifetime,
+            is_optimal,
+        )
+    }
+
+    /// Allocates memory for an image on the physical devices of a device group given by the
+    /// device mask, on the device of a managed allocator. `is_optimal` must be set true if the
+    /// image is a optimal image (a regular texture).
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided image is in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_memory_for_image_with_device_mask_managed(
+        &self,
+        image: vk::Image,
+        location: MemoryLocation,
+        lifetime: LT,
+        is_optimal: bool,
+        device_mask: u32,
+    ) -> Result<Allocation<LT>> {
+        self.allocate_memory_for_image_with_device_mask(
+            self.managed_device()?,
+            image,
+            location,
+            lifetime,
+            is_optimal,
+            device_mask,
+        )
+    }
+
+    /// Allocates memory for a buffer with the location, lifetime, device mask, name and tag of
+    /// the descriptor on the device of a managed allocator.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided buffer is in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_memory_for_buffer_with_descriptor_managed(
+        &self,
+        buffer: vk::Buffer,
+        descriptor: &ResourceAllocationDescriptor<LT>,
+    ) -> Result<Allocation<LT>> {
+        self.allocate_memory_for_buffer_with_descriptor(self.managed_device()?, buffer, descriptor)
+    }
+
+    /// Allocates memory for an image with the location, lifetime, device mask, name and tag of
+    /// the descriptor on the device of a managed allocator. `is_optimal` must be set true if the
+    /// image is a optimal image (a regular texture).
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided image is in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_memory_for_image_with_descriptor_managed(
+        &self,
+        image: vk::Image,
+        is_optimal: bool,
+        descriptor: &ResourceAllocationDescriptor<LT>,
+    ) -> Result<Allocation<LT>> {
+        self.allocate_memory_for_image_with_descriptor(
+            self.managed_device()?,
+            image,
+            is_optimal,
+            descriptor,
+        )
+    }
+
+    /// Binds the memory of the allocation to a buffer on the device of a managed allocator.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided buffer and allocation are in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn bind_buffer_memory_managed(
+        &self,
+        buffer: vk::Buffer,
+        allocation: &Allocation<LT>,
+        device_group_info: Option<&mut vk::BindBufferMemoryDeviceGroupInfo<'_>>,
+    ) -> Result<()> {
+        self.bind_buffer_memory(
+            self.managed_device()?,
+            buffer,
+            allocation,
+            device_group_info,
+        )
+    }
+
+    /// Binds the memory of the allocation to an image on the device of a managed allocator.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided image and allocation are in a valid state.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn bind_image_memory_managed(
+        &self,
+        image: vk::Image,
+        allocation: &Allocation<LT>,
+        device_group_info: Option<&mut vk::BindImageMemoryDeviceGroupInfo<'_>>,
+    ) -> Result<()> {
+        self.bind_image_memory(self.managed_device()?, image, allocation, device_group_info)
+    }
+
+    /// Binds the memory of the allocation to a buffer on the device of a managed allocator and
+    /// registers the device address of the buffer.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided buffer and allocation are in a valid state.
+    #[cfg(feature = "vk-buffer-device-address")]
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn bind_buffer_memory_with_device_address_managed(
+        &self,
+        buffer: vk::Buffer,
+        allocation: &Allocation<LT>,
+    ) -> Result<vk::DeviceAddress> {
+        self.bind_buffer_memory_with_device_address(self.managed_device()?, buffer, allocation)
+    }
+
+    /// Imports memory from a POSIX file descriptor as a dedicated allocation on the device of a
+    /// managed allocator. See `Allocator::import_fd`.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided file descriptor is in a valid state and that
+    /// the `VK_KHR_external_memory_fd` extension is enabled.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn import_fd_managed(
+        &self,
+        fd: i32,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        descriptor: &ImportDescriptor<LT>,
+    ) -> Result<Allocation<LT>> {
+        let handles = self.handles.as_ref().ok_or(AllocatorError::NotManaged)?;
+        let external_memory_fd =
+            ash::khr::external_memory_fd::Device::new(&handles.instance, &handles.device);
+        self.import_fd(
+            &handles.device,
+            &external_memory_fd,
+            fd,
+            handle_type,
+            descriptor,
+        )
+    }
+
+    /// Imports a host pointer as a dedicated allocation on the device of a managed allocator. See
+    /// `Allocator::import_host_pointer`.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the `VK_EXT_external_memory_host` extension is enabled and
+    /// that the host pointer and size are aligned to `minImportedHostPointerAlignment`. The host
+    /// memory needs to stay valid until the allocation is deallocated.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn import_host_pointer_managed(
+        &self,
+        host_pointer: *mut c_void,
+        handle_type: vk::ExternalMemoryHandleTypeFlags,
+        descriptor: &ImportDescriptor<LT>,
+    ) -> Result<Allocation<LT>> {
+        let handles = self.handles.as_ref().ok_or(AllocatorError::NotManaged)?;
+        let external_memory_host =
+            ash::ext::external_memory_host::Device::new(&handles.instance, &handles.device);
+        self.import_host_pointer(
+            &handles.device,
+            &external_memory_host,
+            host_pointer,
+            handle_type,
+            descriptor,
+        )
+    }
+
+    /// Allocates memory on the device of a managed allocator and returns an allocation that
+    /// deallocates itself when dropped.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the allocation is not in use anymore when it's dropped.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_owned_managed(
+        self: &Arc<Self>,
+        descriptor: &AllocationDescriptor<LT>,
+    ) -> Result<OwnedAllocation<LT>> {
+        self.allocate_owned(self.managed_device()?, descriptor)
+    }
+
+    /// Allocates memory on the device of a managed allocator.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the device of the allocator is still valid.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_managed(
+        &self,
+        descriptor: &AllocationDescriptor<LT>,
+    ) -> Result<Allocation<LT>> {
+        self.allocate(self.managed_device()?, descriptor)
+    }
+
+    /// Frees the allocation of a managed allocator.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the allocation is not in use anymore and will not be used
+    /// after being deallocated.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn deallocate_managed(&self, allocation: &Allocation<LT>) -> Result<()> {
+        self.deallocate(self.managed_device()?, allocation)
+    }
+
+    /// Releases all memory blocks of a managed allocator back to the system.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that no allocations are used anymore and will not being used
+    /// after calling this function.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn cleanup_managed(&self) -> Result<Vec<AllocationInfo<LT>>> {
+        Ok(self.cleanup(self.managed_device()?))
+    }
+}
+
+impl<LT: Lifetime> Drop for Allocator<LT> {
+    fn drop(&mut self) {
+        let Some(handles) = self.handles.take() else {
+            return;
+        };
+
+        // SAFETY: A managed allocator owns its device, so no allocation can be used anymore
+        // once the allocator is dropped.
+        let leaks = unsafe { self.cleanup(&handles.device) };
+
+        // `cleanup` already warns about every leak through `tracing`.
+        if let Some(leak_callback) = handles.leak_callback.as_ref() {
+            if !leaks.is_empty() {
+                leak_callback(&leaks);
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Allocations that free themselves on drop.
+
+use std::fmt::Debug;
+use std::mem::ManuallyDrop;
+use std::sync::Arc;
+
+#[cfg(feature = "tracing")]
+use tracing1::warn;
+
+use crate::{Allocation, AllocationDescriptor, Allocator, Lifetime, Result};
+
+/// An allocation that is owned by a single handle and deallocates itself when dropped.
+///
+/// Can't be cloned, so it can't be freed twice. Use `as_allocation` to access the allocation and
+/// `into_raw` to get the raw `Allocation` back.
+pub struct OwnedAllocation<LT: Lifetime> {
+    allocator: Arc<Allocator<LT>>,
+    device: ash::Device,
+    allocation: ManuallyDrop<Allocation<LT>>,
+}
+
+impl<LT: Lifetime> OwnedAllocation<LT> {
+    /// Takes ownership of an allocation of the allocator.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the allocation was allocated by the allocator on the
+    /// provided device, that no other copy of the allocation is deallocated and that the
+    /// allocation is not in use anymore when the handle is dropped.
+    pub unsafe fn new(
+        allocator: Arc<Allocator<LT>>,
+        device: ash::Device,
+        allocation: Allocation<LT>,
+    ) -> Self {
+        Self {
+            allocator,
+            device,
+            allocation: ManuallyDrop::new(allocation),
+        }
+    }
+
+    /// The allocator the allocation belongs to.
+    #[inline]
+    pub fn allocator(&self) -> &Arc<Allocator<LT>> {
+        &self.allocator
+    }
+
+    /// The allocation owned by the handle. It must not be deallocated, since the handle
+    /// deallocates it when dropped.
+    #[inline]
+    pub fn as_allocation(&self) -> &Allocation<LT> {
+        &self.allocation
+    }
+
+    /// Returns a valid mapped mutable slice if the memory is host visible, otherwise it will return None.
+    /// The slice already references the exact memory region of the sub allocation, so no offset needs to be applied.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the allocation is still valid and coherent.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn mapped_slice_mut(&mut self) -> Result<Option<&mut [u8]>> {
+        self.allocation.mapped_slice_mut()
+    }
+
+    /// Releases the ownership and returns the raw allocation. The caller is responsible for
+    /// deallocating it.
+    pub fn into_raw(self) -> Allocation<LT> {
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never used or dropped again, so the fields are only moved out once.
+        unsafe {
+            std::ptr::drop_in_place(&mut this.allocator);
+            std::ptr::drop_in_place(&mut this.device);
+            ManuallyDrop::take(&mut this.allocation)
+        }
+    }
+}
+
+impl<LT: Lifetime> Debug for OwnedAllocation<LT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OwnedAllocation")
+            .field("device", &self.device.handle())
+            .field("allocation", &*self.allocation)
+            .finish()
+    }
+}
+
+impl<LT: Lifetime> Drop for OwnedAllocation<LT> {
+    fn drop(&mut self) {
+        // SAFETY: The allocation is only taken once, since this is the only place besides
+        // `into_raw` that takes it, and `into_raw` never runs the drop.
+        let allocation = unsafe { ManuallyDrop::take(&mut self.allocation) };
+        #[allow(unused_variables)]
+        if let Err(err) = unsafe { self.allocator.deallocate(&self.device, &allocation) } {
+            #[cfg(feature = "tracing")]
+            warn!("Can't deallocate owned allocation: {}", err);
+        }
+    }
+}
+
+impl<LT: Lifetime> Allocator<LT> {
+    /// Allocates memory on the allocator and returns an allocation that deallocates itself when
+    /// dropped.
+    ///
+    /// # Safety
+    /// Caller needs to make sure that the provided device is in a valid state and that the
+    /// allocation is not in use anymore when it's dropped.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub unsafe fn allocate_owned(
+        self: &Arc<Self>,
+        device: &ash::Device,
+        descriptor: &AllocationDescriptor<LT>,
+    ) -> Result<OwnedAllocation<LT>> {
+        let allocation = self.allocate(device, descriptor)?;
+        Ok(OwnedAllocation::new(
+            self.clone(),
+            device.clone(),
+            allocation,
+        ))
+    }
+}
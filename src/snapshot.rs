@@ -0,0 +1,83 @@
+//! Snapshots of the live allocations.
+
+use std::collections::HashMap;
+
+use crate::{AllocationInfo, Allocator, Lifetime};
+
+/// The live allocations of an allocator at one point in time, keyed by their id.
+#[derive(Clone, Debug, Default)]
+pub struct AllocationSnapshot<LT: Lifetime> {
+    /// The live allocations.
+    pub allocations: HashMap<u64, AllocationInfo<LT>>,
+}
+
+/// The allocations that were created and freed between two snapshots, ordered by their id.
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotDiff<LT: Lifetime> {
+    /// Allocations that are only part of the later snapshot.
+    pub created: Vec<AllocationInfo<LT>>,
+    /// Allocations that are only part of the earlier snapshot.
+    pub freed: Vec<AllocationInfo<LT>>,
+}
+
+impl<LT: Lifetime> AllocationSnapshot<LT> {
+    /// Returns the allocations that were created and freed between the two snapshots.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn diff(
+        before: &AllocationSnapshot<LT>,
+        after: &AllocationSnapshot<LT>,
+    ) -> SnapshotDiff<LT> {
+        let mut created: Vec<_> = after
+            .allocations
+            .iter()
+            .filter(|(id, _)| !before.allocations.contains_key(id))
+            .map(|(_, info)| info.clone())
+            .collect();
+        let mut freed: Vec<_> = before
+            .allocations
+            .iter()
+            .filter(|(id, _)| !after.allocations.contains_key(id))
+            .map(|(_, info)| info.clone())
+            .collect();
+
+        created.sort_by_key(|info| info.id);
+        freed.sort_by_key(|info| info.id);
+
+        SnapshotDiff { created, freed }
+    }
+
+    /// Returns true if the snapshot contains an allocation of the lifetime.
+    pub fn contains_lifetime(&self, lifetime: LT) -> bool {
+        self.allocations
+            .values()
+            .any(|info| info.lifetime == lifetime)
+    }
+}
+
+impl<LT: Lifetime> SnapshotDiff<LT> {
+    /// Returns true if no allocations were created or freed.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.freed.is_empty()
+    }
+}
+
+impl<LT: Lifetime> Allocator<LT> {
+    /// Takes a snapshot of all live allocations.
+    #[cfg_attr(feature = "profiling", profiling::function)]
+    pub fn snapshot(&self) -> AllocationSnapshot<LT> {
+        let mut allocations = Vec::new();
+        for lifetime_pools in self.pools.read().values() {
+            lifetime_pools
+                .iter()
+                .for_each(|pool| pool.lock().collect_allocations(&mut allocations));
+        }
+
+        AllocationSnapshot {
+            allocations: allocations
+                .into_iter()
+                .map(|info| (info.id, info))
+                .collect(),
+        }
+    }
+}
@@ -36,6 +36,9 @@ pub struct VulkanContext {
     pub physical_device: vk::PhysicalDevice,
     pub queue: vk::Queue,
     pub buffer_image_granularity: vk::DeviceSize,
+    // If the optional external memory extensions are enabled on the logical device.
+    pub external_memory_fd: bool,
+    pub external_memory_host: bool,
 }
 
 impl Drop for VulkanContext {
@@ -72,7 +75,11 @@ impl VulkanContext {
         let extensions = Self::create_instance_extensions(&entry);
         let instance_layers = Self::create_layers(&entry);
         let instance = Self::create_instance(&entry, &app_info, &extensions, &instance_layers);
-        let (physical_device, logical_device, queue) = Self::request_device(&instance);
+        let (physical_device, logical_device, queue, enabled_extensions) =
+            Self::request_device(&instance);
+        let external_memory_fd = enabled_extensions.contains(&ash::khr::external_memory_fd::NAME);
+        let external_memory_host =
+            enabled_extensions.contains(&ash::ext::external_memory_host::NAME);
 
         let physical_device_properties =
             unsafe { instance.get_physical_device_properties(physical_device) };
@@ -90,6 +97,8 @@ impl VulkanContext {
                 logical_device,
                 queue,
                 buffer_image_granularity,
+                external_memory_fd,
+                external_memory_host,
                 debug_messenger,
                 debug_utils_ext,
             }
@@ -104,6 +113,8 @@ impl VulkanContext {
                 logical_device,
                 queue,
                 buffer_image_granularity,
+                external_memory_fd,
+                external_memory_host,
             }
         }
     }
@@ -195,7 +206,14 @@ impl VulkanContext {
         (debug_messenger, instance)
     }
 
-    fn request_device(instance: &ash::Instance) -> (vk::PhysicalDevice, ash::Device, vk::Queue) {
+    fn request_device(
+        instance: &ash::Instance,
+    ) -> (
+        vk::PhysicalDevice,
+        ash::Device,
+        vk::Queue,
+        Vec<&'static CStr>,
+    ) {
         let physical_devices = unsafe { instance.enumerate_physical_devices().unwrap() };
 
         let mut chosen = None;
@@ -210,15 +228,16 @@ impl VulkanContext {
         }
 
         let (physical_device, _) = chosen.unwrap();
-        let (logical_device, queue) = Self::create_logical_device(instance, physical_device);
+        let (logical_device, queue, enabled_extensions) =
+            Self::create_logical_device(instance, physical_device);
 
-        (physical_device, logical_device, queue)
+        (physical_device, logical_device, queue, enabled_extensions)
     }
 
     fn create_logical_device(
         instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
-    ) -> (ash::Device, vk::Queue) {
+    ) -> (ash::Device, vk::Queue, Vec<&'static CStr>) {
         let queue_family_properties =
             unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
 
@@ -228,10 +247,11 @@ impl VulkanContext {
         let queue_infos = [vk::DeviceQueueCreateInfo::default()
             .queue_family_index(transfer_queue_family_id)
             .queue_priorities(&[1.0])];
-        let logical_device = Self::create_device(instance, physical_device, &queue_infos);
+        let (logical_device, enabled_extensions) =
+            Self::create_device(instance, physical_device, &queue_infos);
         let queue = unsafe { logical_device.get_device_queue(transfer_queue_family_id, 0) };
 
-        (logical_device, queue)
+        (logical_device, queue, enabled_extensions)
     }
 
     fn find_queue_family(
@@ -260,20 +280,23 @@ impl VulkanContext {
         instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
         queue_infos: &[vk::DeviceQueueCreateInfo],
-    ) -> ash::Device {
-        let device_extensions = Self::create_device_extensions(instance, physical_device);
+    ) -> (ash::Device, Vec<&'static CStr>) {
+        let (device_extensions, enabled_extensions) =
+            Self::create_device_extensions(instance, physical_device);
 
         let device_create_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(queue_infos)
             .enabled_extension_names(&device_extensions);
 
-        unsafe { instance.create_device(physical_device, &device_create_info, None) }.unwrap()
+        let device =
+            unsafe { instance.create_device(physical_device, &device_create_info, None) }.unwrap();
+        (device, enabled_extensions)
     }
 
     fn create_device_extensions(
         instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
-    ) -> Vec<*const c_char> {
+    ) -> (Vec<*const c_char>, Vec<&'static CStr>) {
         let mut extensions = Vec::new();
 
         let device_extensions =
@@ -294,7 +317,25 @@ impl VulkanContext {
             }
         });
 
-        extensions
+        // Optional extensions are only enabled if the device supports them.
+        let optional_extensions: Vec<&'static CStr> = [
+            ash::khr::external_memory_fd::NAME,
+            ash::ext::external_memory_host::NAME,
+        ]
+        .into_iter()
+        .filter(|extension| {
+            device_extensions.iter().any(|device_ext| unsafe {
+                CStr::from_ptr(device_ext.extension_name.as_ptr()) == *extension
+            })
+        })
+        .collect();
+        extensions.extend(
+            optional_extensions
+                .iter()
+                .map(|extension| extension.as_ptr()),
+        );
+
+        (extensions, optional_extensions)
     }
 }
 
@@ -1,7 +1,17 @@
+use std::ffi::{c_char, c_void, CStr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use ash::prelude::VkResult;
 use ash::vk;
+use ash::vk::Handle;
 use romu::Rng;
 
-use ash_alloc::{Allocation, AllocationDescriptor, Allocator, AllocatorDescriptor, MemoryLocation};
+use ash_alloc::{
+    Allocation, AllocationDescriptor, AllocationLookup, AllocationSnapshot, Allocator,
+    AllocatorDescriptor, AllocatorError, DebugFillPatterns, DeviceMemoryCallbacks,
+    ImportDescriptor, MemoryBlockInfo, MemoryHook, MemoryLocation, ResourceAllocationDescriptor,
+};
 
 pub mod fixture;
 
@@ -39,7 +49,10 @@ fn allocator_simple_free() {
         let alloc = Allocator::new(
             &ctx.instance,
             ctx.physical_device,
-            &AllocatorDescriptor { block_size: 20 }, // 1 MiB
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -55,6 +68,10 @@ fn allocator_simple_free() {
                     lifetime: TestLifetime::Static,
                     is_dedicated: false,
                     is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
                 },
             )
             .unwrap();
@@ -80,7 +97,10 @@ fn allocator_allocation_1024() {
         let alloc = Allocator::new(
             &ctx.instance,
             ctx.physical_device,
-            &AllocatorDescriptor { block_size: 20 }, // 1 MiB
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -98,6 +118,10 @@ fn allocator_allocation_1024() {
                             lifetime: TestLifetime::Static,
                             is_dedicated: false,
                             is_optimal: false,
+                            device_mask: 0,
+                            memory_hook: None,
+                            name: None,
+                            tag: 0,
                         },
                     )
                     .unwrap();
@@ -139,7 +163,10 @@ fn allocator_allocation_256() {
         let alloc = Allocator::new(
             &ctx.instance,
             ctx.physical_device,
-            &AllocatorDescriptor { block_size: 20 }, // 1 MiB
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -157,6 +184,10 @@ fn allocator_allocation_256() {
                             lifetime: TestLifetime::Static,
                             is_dedicated: false,
                             is_optimal: false,
+                            device_mask: 0,
+                            memory_hook: None,
+                            name: None,
+                            tag: 0,
                         },
                     )
                     .unwrap();
@@ -201,7 +232,10 @@ fn allocator_reverse_free() {
         let alloc = Allocator::new(
             &ctx.instance,
             ctx.physical_device,
-            &AllocatorDescriptor { block_size: 20 }, // 1 MiB
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -219,6 +253,10 @@ fn allocator_reverse_free() {
                             lifetime: TestLifetime::Static,
                             is_dedicated: false,
                             is_optimal: false,
+                            device_mask: 0,
+                            memory_hook: None,
+                            name: None,
+                            tag: 0,
                         },
                     )
                     .unwrap();
@@ -272,7 +310,10 @@ fn allocator_free_every_second_time() {
         let alloc = Allocator::new(
             &ctx.instance,
             ctx.physical_device,
-            &AllocatorDescriptor { block_size: 20 }, // 1 MiB
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -290,6 +331,10 @@ fn allocator_free_every_second_time() {
                             lifetime: TestLifetime::Static,
                             is_dedicated: false,
                             is_optimal: false,
+                            device_mask: 0,
+                            memory_hook: None,
+                            name: None,
+                            tag: 0,
                         },
                     )
                     .unwrap()
@@ -333,7 +378,10 @@ fn allocator_allocation_dedicated() {
         let alloc = Allocator::new(
             &ctx.instance,
             ctx.physical_device,
-            &AllocatorDescriptor { block_size: 20 }, // 1 MiB
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -349,6 +397,10 @@ fn allocator_allocation_dedicated() {
                     lifetime: TestLifetime::Static,
                     is_dedicated: false,
                     is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
                 },
             )
             .unwrap();
@@ -378,7 +430,10 @@ fn allocator_properly_merge_free_entries() {
         let alloc = Allocator::new(
             &ctx.instance,
             ctx.physical_device,
-            &AllocatorDescriptor { block_size: 20 }, // 1 MiB
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -394,6 +449,10 @@ fn allocator_properly_merge_free_entries() {
                     lifetime: TestLifetime::Static,
                     is_dedicated: false,
                     is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
                 },
             )
             .unwrap();
@@ -409,6 +468,10 @@ fn allocator_properly_merge_free_entries() {
                     lifetime: TestLifetime::Static,
                     is_dedicated: false,
                     is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
                 },
             )
             .unwrap();
@@ -424,6 +487,10 @@ fn allocator_properly_merge_free_entries() {
                     lifetime: TestLifetime::Static,
                     is_dedicated: false,
                     is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
                 },
             )
             .unwrap();
@@ -439,6 +506,10 @@ fn allocator_properly_merge_free_entries() {
                     lifetime: TestLifetime::Static,
                     is_dedicated: false,
                     is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
                 },
             )
             .unwrap();
@@ -485,6 +556,10 @@ fn allocator_fuzzy() {
                             lifetime: TestLifetime::Static,
                             is_dedicated: false,
                             is_optimal: false,
+                            device_mask: 0,
+                            memory_hook: None,
+                            name: None,
+                            tag: 0,
                         },
                     )
                     .unwrap();
@@ -526,7 +601,10 @@ fn allocator_granularity() {
         let alloc = Allocator::new(
             &ctx.instance,
             ctx.physical_device,
-            &AllocatorDescriptor { block_size: 20 }, // 1 MiB
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
         )
         .unwrap();
 
@@ -542,6 +620,10 @@ fn allocator_granularity() {
                     lifetime: TestLifetime::Static,
                     is_dedicated: false,
                     is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
                 },
             )
             .unwrap();
@@ -563,6 +645,10 @@ fn allocator_granularity() {
                     lifetime: TestLifetime::Static,
                     is_dedicated: false,
                     is_optimal: true,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
                 },
             )
             .unwrap();
@@ -584,6 +670,10 @@ fn allocator_granularity() {
                     lifetime: TestLifetime::Static,
                     is_dedicated: false,
                     is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
                 },
             )
             .unwrap();
@@ -594,3 +684,2375 @@ fn allocator_granularity() {
         alloc.cleanup(&ctx.logical_device);
     }
 }
+
+#[derive(Debug, Default)]
+struct CountingHook {
+    allocated: AtomicUsize,
+    freed: AtomicUsize,
+}
+
+impl MemoryHook for CountingHook {
+    fn allocate_memory(
+        &self,
+        info: vk::MemoryAllocateInfo<'_>,
+        _block_info: &MemoryBlockInfo,
+        allocate: &mut dyn FnMut(&vk::MemoryAllocateInfo<'_>) -> VkResult<vk::DeviceMemory>,
+    ) -> VkResult<vk::DeviceMemory> {
+        let mut dedicated_info = vk::MemoryDedicatedAllocateInfo::default();
+        allocate(&info.push_next(&mut dedicated_info))
+    }
+
+    fn after_allocate(&self, _device_memory: vk::DeviceMemory, _block_info: &MemoryBlockInfo) {
+        self.allocated.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn before_free(&self, _device_memory: vk::DeviceMemory, _block_info: &MemoryBlockInfo) {
+        self.freed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn allocator_memory_hook() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let pool_hook = Arc::new(CountingHook::default());
+        let allocation_hook = Arc::new(CountingHook::default());
+        alloc.set_memory_hook(TestLifetime::Static, Some(pool_hook.clone()));
+
+        let a0 = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    location: MemoryLocation::GpuOnly,
+                    requirements: vk::MemoryRequirements::default()
+                        .alignment(256)
+                        .size(256)
+                        .memory_type_bits(u32::MAX),
+                    lifetime: TestLifetime::Static,
+                    is_dedicated: false,
+                    is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
+                },
+            )
+            .unwrap();
+        let a1 = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    location: MemoryLocation::GpuOnly,
+                    requirements: vk::MemoryRequirements::default()
+                        .alignment(256)
+                        .size(256)
+                        .memory_type_bits(u32::MAX),
+                    lifetime: TestLifetime::Static,
+                    is_dedicated: false,
+                    is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: Some(allocation_hook.clone()),
+                    name: None,
+                    tag: 0,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(pool_hook.allocated.load(Ordering::SeqCst), 1);
+        assert_eq!(allocation_hook.allocated.load(Ordering::SeqCst), 1);
+        assert_ne!(a0.device_memory(), a1.device_memory());
+
+        alloc.deallocate(&ctx.logical_device, &a1).unwrap();
+        assert_eq!(allocation_hook.freed.load(Ordering::SeqCst), 1);
+
+        alloc.deallocate(&ctx.logical_device, &a0).unwrap();
+        alloc.cleanup(&ctx.logical_device);
+        assert_eq!(pool_hook.freed.load(Ordering::SeqCst), 1);
+    }
+}
+
+#[test]
+fn allocator_device_memory_callbacks() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+
+        let allocated = Arc::new(AtomicUsize::new(0));
+        let freed = Arc::new(AtomicUsize::new(0));
+        let allocated_bytes = allocated.clone();
+        let freed_bytes = freed.clone();
+
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                device_memory_callbacks: Some(DeviceMemoryCallbacks {
+                    allocate: Some(Arc::new(move |info| {
+                        assert_eq!(info.lifetime, TestLifetime::Static);
+                        allocated_bytes.fetch_add(info.size as usize, Ordering::SeqCst);
+                    })),
+                    free: Some(Arc::new(move |info| {
+                        freed_bytes.fetch_add(info.size as usize, Ordering::SeqCst);
+                    })),
+                }),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let sub_allocation = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    location: MemoryLocation::GpuOnly,
+                    requirements: vk::MemoryRequirements::default()
+                        .alignment(256)
+                        .size(256)
+                        .memory_type_bits(u32::MAX),
+                    lifetime: TestLifetime::Static,
+                    is_dedicated: false,
+                    is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
+                },
+            )
+            .unwrap();
+        let dedicated_allocation = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    location: MemoryLocation::GpuOnly,
+                    requirements: vk::MemoryRequirements::default()
+                        .alignment(256)
+                        .size(1024)
+                        .memory_type_bits(u32::MAX),
+                    lifetime: TestLifetime::Static,
+                    is_dedicated: true,
+                    is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
+                },
+            )
+            .unwrap();
+
+        assert_eq!(allocated.load(Ordering::SeqCst), 1024 * 1024 + 1024);
+
+        alloc
+            .deallocate(&ctx.logical_device, &dedicated_allocation)
+            .unwrap();
+        assert_eq!(freed.load(Ordering::SeqCst), 1024);
+
+        alloc
+            .deallocate(&ctx.logical_device, &sub_allocation)
+            .unwrap();
+        alloc.cleanup(&ctx.logical_device);
+        assert_eq!(freed.load(Ordering::SeqCst), 1024 * 1024 + 1024);
+    }
+}
+
+static HOST_ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static HOST_FREES: AtomicUsize = AtomicUsize::new(0);
+
+// The size and alignment of a host allocation are stored in front of the returned pointer.
+const HOST_HEADER_SIZE: usize = 2 * std::mem::size_of::<usize>();
+
+unsafe fn host_allocate(size: usize, alignment: usize) -> *mut c_void {
+    let alignment = alignment.max(HOST_HEADER_SIZE);
+    let layout = std::alloc::Layout::from_size_align(size + alignment, alignment).unwrap();
+    let base = std::alloc::alloc(layout);
+    if base.is_null() {
+        return std::ptr::null_mut();
+    }
+    let memory = base.add(alignment);
+    memory
+        .sub(HOST_HEADER_SIZE)
+        .cast::<[usize; 2]>()
+        .write([size, alignment]);
+    memory.cast()
+}
+
+unsafe fn host_free(memory: *mut c_void) {
+    let memory = memory.cast::<u8>();
+    let [size, alignment] = memory.sub(HOST_HEADER_SIZE).cast::<[usize; 2]>().read();
+    let layout = std::alloc::Layout::from_size_align(size + alignment, alignment).unwrap();
+    std::alloc::dealloc(memory.sub(alignment), layout);
+}
+
+unsafe extern "system" fn counting_allocation(
+    _user_data: *mut c_void,
+    size: usize,
+    alignment: usize,
+    _scope: vk::SystemAllocationScope,
+) -> *mut c_void {
+    HOST_ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+    host_allocate(size, alignment)
+}
+
+unsafe extern "system" fn counting_reallocation(
+    _user_data: *mut c_void,
+    original: *mut c_void,
+    size: usize,
+    alignment: usize,
+    _scope: vk::SystemAllocationScope,
+) -> *mut c_void {
+    if original.is_null() {
+        HOST_ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        return host_allocate(size, alignment);
+    }
+    if size == 0 {
+        HOST_FREES.fetch_add(1, Ordering::SeqCst);
+        host_free(original);
+        return std::ptr::null_mut();
+    }
+
+    let memory = host_allocate(size, alignment);
+    if !memory.is_null() {
+        let [original_size, _] = original
+            .cast::<u8>()
+            .sub(HOST_HEADER_SIZE)
+            .cast::<[usize; 2]>()
+            .read();
+        std::ptr::copy_nonoverlapping(
+            original.cast::<u8>(),
+            memory.cast::<u8>(),
+            original_size.min(size),
+        );
+        host_free(original);
+    }
+    memory
+}
+
+unsafe extern "system" fn counting_free(_user_data: *mut c_void, memory: *mut c_void) {
+    if !memory.is_null() {
+        HOST_FREES.fetch_add(1, Ordering::SeqCst);
+        host_free(memory);
+    }
+}
+
+#[test]
+fn allocator_allocation_callbacks() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let allocation_callbacks = vk::AllocationCallbacks::default()
+            .pfn_allocation(Some(counting_allocation))
+            .pfn_reallocation(Some(counting_reallocation))
+            .pfn_free(Some(counting_free));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                allocation_callbacks: Some(allocation_callbacks),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(alloc.allocation_callbacks().is_some());
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+        let sub_allocation = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let dedicated_allocation = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    is_dedicated: true,
+                    ..descriptor
+                },
+            )
+            .unwrap();
+
+        // The host memory of the memory objects is allocated through the callbacks.
+        assert!(HOST_ALLOCATIONS.load(Ordering::SeqCst) > 0);
+
+        alloc
+            .deallocate(&ctx.logical_device, &dedicated_allocation)
+            .unwrap();
+        alloc
+            .deallocate(&ctx.logical_device, &sub_allocation)
+            .unwrap();
+        alloc.cleanup(&ctx.logical_device);
+
+        // Freeing the memory objects releases their host memory through the callbacks.
+        assert!(HOST_FREES.load(Ordering::SeqCst) > 0);
+        assert_eq!(
+            HOST_ALLOCATIONS.load(Ordering::SeqCst),
+            HOST_FREES.load(Ordering::SeqCst)
+        );
+    }
+}
+#[test]
+fn allocator_owned_allocation() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Arc::new(
+            Allocator::new(
+                &ctx.instance,
+                ctx.physical_device,
+                &AllocatorDescriptor {
+                    block_size: 20, // 1 MiB
+                    ..Default::default()
+                },
+            )
+            .unwrap(),
+        );
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        // Dropping the handle frees the allocation.
+        let owned = alloc
+            .allocate_owned(&ctx.logical_device, &descriptor)
+            .unwrap();
+        assert_eq!(owned.as_allocation().size(), 1024);
+        assert_eq!(alloc.allocation_count(), 1);
+
+        drop(owned);
+        assert_eq!(alloc.allocation_count(), 0);
+        assert_eq!(alloc.used_bytes(), 0);
+
+        // `into_raw` hands the allocation back without freeing it.
+        let owned = alloc
+            .allocate_owned(&ctx.logical_device, &descriptor)
+            .unwrap();
+        let id = owned.as_allocation().id();
+        let allocation = owned.into_raw();
+        assert_eq!(allocation.id(), id);
+        assert_eq!(alloc.allocation_count(), 1);
+        assert!(alloc.snapshot().allocations.contains_key(&id));
+
+        alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+        assert_eq!(alloc.allocation_count(), 0);
+
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_stale_allocation() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::CpuToGpu,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        let stale = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        alloc.deallocate(&ctx.logical_device, &stale).unwrap();
+        assert_eq!(
+            alloc.deallocate(&ctx.logical_device, &stale),
+            Err(AllocatorError::StaleAllocation)
+        );
+
+        // The new allocation reuses the chunk of the stale allocation.
+        let allocation = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        assert_eq!(allocation.offset(), stale.offset());
+        assert_eq!(
+            alloc.deallocate(&ctx.logical_device, &stale),
+            Err(AllocatorError::StaleAllocation)
+        );
+        assert!(alloc.mapped_slice(&stale).is_err());
+        assert!(alloc.mapped_slice(&allocation).unwrap().is_some());
+        assert_eq!(alloc.allocation_count(), 1);
+
+        alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+
+        let dedicated = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    is_dedicated: true,
+                    ..descriptor.clone()
+                },
+            )
+            .unwrap();
+        alloc.deallocate(&ctx.logical_device, &dedicated).unwrap();
+        assert_eq!(
+            alloc.deallocate(&ctx.logical_device, &dedicated),
+            Err(AllocatorError::StaleAllocation)
+        );
+
+        // Cleanup recreates the pools, so the new allocation gets the same slot as the one from
+        // before the cleanup.
+        let before_cleanup = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        alloc.cleanup(&ctx.logical_device);
+        let allocation = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        assert_eq!(allocation.offset(), before_cleanup.offset());
+        assert_eq!(
+            alloc.deallocate(&ctx.logical_device, &before_cleanup),
+            Err(AllocatorError::StaleAllocation)
+        );
+        assert!(alloc.mapped_slice(&before_cleanup).is_err());
+        assert_eq!(alloc.allocation_count(), 1);
+        alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[cfg(feature = "backtrace")]
+#[test]
+fn allocator_backtrace() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        let allocation = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let dedicated = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    is_dedicated: true,
+                    ..descriptor
+                },
+            )
+            .unwrap();
+
+        // The backtraces are captured even if RUST_BACKTRACE is not set and point at the caller.
+        let mut infos = Vec::new();
+        alloc.for_each_allocation(|info| infos.push(info.clone()));
+        assert_eq!(infos.len(), 2);
+        for info in infos.iter() {
+            let backtrace = info.backtrace.as_ref().unwrap();
+            assert_eq!(
+                backtrace.status(),
+                std::backtrace::BacktraceStatus::Captured
+            );
+            assert!(backtrace.to_string().contains("allocator_backtrace"));
+        }
+
+        alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+        alloc.deallocate(&ctx.logical_device, &dedicated).unwrap();
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_managed() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let unmanaged = Allocator::<TestLifetime>::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor::default(),
+        )
+        .unwrap();
+        assert!(unmanaged.device().is_none());
+        assert_eq!(
+            unmanaged
+                .allocate_memory_for_buffer_managed(
+                    vk::Buffer::null(),
+                    MemoryLocation::GpuOnly,
+                    TestLifetime::Static
+                )
+                .unwrap_err(),
+            AllocatorError::NotManaged
+        );
+        assert_eq!(
+            unmanaged
+                .import_host_pointer_managed(
+                    std::ptr::null_mut(),
+                    vk::ExternalMemoryHandleTypeFlags::HOST_ALLOCATION_EXT,
+                    &ImportDescriptor {
+                        location: MemoryLocation::CpuToGpu,
+                        requirements: vk::MemoryRequirements::default(),
+                        lifetime: TestLifetime::Static,
+                        device_mask: 0,
+                        name: None,
+                        tag: 0,
+                    },
+                )
+                .unwrap_err(),
+            AllocatorError::NotManaged
+        );
+        assert_eq!(
+            unmanaged.cleanup_managed().unwrap_err(),
+            AllocatorError::NotManaged
+        );
+
+        let leaks = Arc::new(Mutex::new(Vec::new()));
+        let reported_leaks = leaks.clone();
+        let alloc = Allocator::new_managed(
+            &ctx.instance,
+            ctx.physical_device,
+            &ctx.logical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                leak_callback: Some(Arc::new(move |leaks| {
+                    reported_leaks.lock().unwrap().extend_from_slice(leaks);
+                })),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            alloc.device().map(|device| device.handle()),
+            Some(ctx.logical_device.handle())
+        );
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::CpuToGpu,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        let allocation = alloc.allocate_managed(&descriptor).unwrap();
+        alloc.deallocate_managed(&allocation).unwrap();
+
+        let buffer = ctx
+            .logical_device
+            .create_buffer(
+                &vk::BufferCreateInfo::default()
+                    .size(1024)
+                    .usage(vk::BufferUsageFlags::TRANSFER_DST)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE),
+                None,
+            )
+            .unwrap();
+        let buffer_allocation = alloc
+            .allocate_memory_for_buffer_with_device_mask_managed(
+                buffer,
+                MemoryLocation::GpuOnly,
+                TestLifetime::Static,
+                0,
+            )
+            .unwrap();
+        alloc
+            .bind_buffer_memory_managed(buffer, &buffer_allocation, None)
+            .unwrap();
+        ctx.logical_device.destroy_buffer(buffer, None);
+        alloc.deallocate_managed(&buffer_allocation).unwrap();
+
+        // Leaked allocations are freed and reported when the allocator is dropped.
+        let leaked = alloc
+            .allocate_managed(&AllocationDescriptor {
+                name: Some("leaked".into()),
+                tag: 3,
+                ..descriptor
+            })
+            .unwrap();
+        assert_eq!(alloc.allocation_count(), 1);
+        assert!(leaks.lock().unwrap().is_empty());
+        drop(alloc);
+
+        let leaks = leaks.lock().unwrap();
+        assert_eq!(leaks.len(), 1);
+        assert_eq!(leaks[0].id, leaked.id());
+        assert_eq!(leaks[0].name.as_deref(), Some("leaked"));
+        assert_eq!(leaks[0].tag, 3);
+        assert_eq!(leaks[0].size, 1024);
+    }
+}
+
+#[test]
+fn allocator_leak_report() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        let freed = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let leaked = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let leaked_dedicated = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    is_dedicated: true,
+                    ..descriptor
+                },
+            )
+            .unwrap();
+        alloc.deallocate(&ctx.logical_device, &freed).unwrap();
+
+        let leaks = alloc.check_leaks();
+        assert_eq!(leaks.len(), 2);
+        assert!(leaks.iter().any(|leak| !leak.is_dedicated
+            && leak.device_memory == leaked.device_memory()
+            && leak.offset == leaked.offset()
+            && leak.size == leaked.size()));
+        assert!(leaks.iter().any(|leak| leak.is_dedicated
+            && leak.device_memory == leaked_dedicated.device_memory()
+            && leak.lifetime == TestLifetime::Static));
+
+        #[cfg(feature = "backtrace")]
+        assert!(leaks.iter().all(|leak| leak.backtrace.is_some()));
+
+        assert_eq!(alloc.cleanup(&ctx.logical_device), leaks);
+        assert!(alloc.check_leaks().is_empty());
+    }
+}
+
+#[test]
+fn allocator_allocation_names() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: Some("vertex buffer".into()),
+            tag: 42,
+        };
+
+        let allocation = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        assert_eq!(allocation.name(), Some("vertex buffer"));
+        assert_eq!(allocation.tag(), 42);
+
+        let dedicated = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    is_dedicated: true,
+                    name: Some(format!("texture {}", 7).into()),
+                    tag: 7,
+                    ..descriptor.clone()
+                },
+            )
+            .unwrap();
+        assert_eq!(dedicated.name(), Some("texture 7"));
+
+        let leaks = alloc.check_leaks();
+        assert!(leaks
+            .iter()
+            .any(|leak| leak.name.as_deref() == Some("vertex buffer") && leak.tag == 42));
+        assert!(leaks
+            .iter()
+            .any(|leak| leak.name.as_deref() == Some("texture 7") && leak.tag == 7));
+
+        // A freed chunk doesn't keep the name of its previous allocation.
+        alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+        let unnamed = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    name: None,
+                    tag: 0,
+                    ..descriptor
+                },
+            )
+            .unwrap();
+        assert_eq!(unnamed.name(), None);
+
+        // Buffers and images can be named through their descriptor.
+        let buffer = ctx
+            .logical_device
+            .create_buffer(
+                &vk::BufferCreateInfo::default()
+                    .size(1024)
+                    .usage(vk::BufferUsageFlags::TRANSFER_DST)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE),
+                None,
+            )
+            .unwrap();
+        let buffer_allocation = alloc
+            .allocate_memory_for_buffer_with_descriptor(
+                &ctx.logical_device,
+                buffer,
+                &ResourceAllocationDescriptor {
+                    location: MemoryLocation::GpuOnly,
+                    lifetime: TestLifetime::Static,
+                    device_mask: 0,
+                    name: Some("index buffer".into()),
+                    tag: 11,
+                },
+            )
+            .unwrap();
+        assert_eq!(buffer_allocation.name(), Some("index buffer"));
+        assert_eq!(buffer_allocation.tag(), 11);
+        let image = ctx
+            .logical_device
+            .create_image(
+                &vk::ImageCreateInfo::default()
+                    .image_type(vk::ImageType::TYPE_2D)
+                    .format(vk::Format::R8G8B8A8_UNORM)
+                    .extent(vk::Extent3D {
+                        width: 64,
+                        height: 64,
+                        depth: 1,
+                    })
+                    .mip_levels(1)
+                    .array_layers(1)
+                    .samples(vk::SampleCountFlags::TYPE_1)
+                    .tiling(vk::ImageTiling::OPTIMAL)
+                    .usage(vk::ImageUsageFlags::SAMPLED)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                    .initial_layout(vk::ImageLayout::UNDEFINED),
+                None,
+            )
+            .unwrap();
+        let image_allocation = alloc
+            .allocate_memory_for_image_with_descriptor(
+                &ctx.logical_device,
+                image,
+                true,
+                &ResourceAllocationDescriptor {
+                    location: MemoryLocation::GpuOnly,
+                    lifetime: TestLifetime::Static,
+                    device_mask: 0,
+                    name: Some("albedo".into()),
+                    tag: 12,
+                },
+            )
+            .unwrap();
+        assert!(alloc
+            .check_leaks()
+            .iter()
+            .any(|leak| leak.id == image_allocation.id()
+                && leak.name.as_deref() == Some("albedo")
+                && leak.tag == 12));
+        alloc
+            .deallocate(&ctx.logical_device, &buffer_allocation)
+            .unwrap();
+        alloc
+            .deallocate(&ctx.logical_device, &image_allocation)
+            .unwrap();
+        ctx.logical_device.destroy_buffer(buffer, None);
+        ctx.logical_device.destroy_image(image, None);
+
+        alloc.deallocate(&ctx.logical_device, &unnamed).unwrap();
+        alloc.deallocate(&ctx.logical_device, &dedicated).unwrap();
+        assert!(alloc.check_leaks().is_empty());
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+// The object names set through `vkSetDebugUtilsObjectNameEXT`, keyed by the object handle.
+static OBJECT_NAMES: Mutex<Vec<(u64, String)>> = Mutex::new(Vec::new());
+
+unsafe extern "system" fn recording_set_object_name(
+    _device: vk::Device,
+    name_info: *const vk::DebugUtilsObjectNameInfoEXT<'_>,
+) -> vk::Result {
+    let name_info = &*name_info;
+    let name = CStr::from_ptr(name_info.p_object_name)
+        .to_string_lossy()
+        .into_owned();
+    OBJECT_NAMES
+        .lock()
+        .unwrap()
+        .push((name_info.object_handle, name));
+    vk::Result::SUCCESS
+}
+
+unsafe extern "system" fn recording_get_device_proc_addr(
+    _device: vk::Device,
+    name: *const c_char,
+) -> vk::PFN_vkVoidFunction {
+    if CStr::from_ptr(name) == c"vkSetDebugUtilsObjectNameEXT" {
+        let set_object_name: vk::PFN_vkSetDebugUtilsObjectNameEXT = recording_set_object_name;
+        Some(std::mem::transmute::<
+            vk::PFN_vkSetDebugUtilsObjectNameEXT,
+            unsafe extern "system" fn(),
+        >(set_object_name))
+    } else {
+        None
+    }
+}
+
+#[test]
+fn allocator_debug_utils_names() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        // Vulkan can't query object names, so the names are recorded by loading the debug utils
+        // functions through an instance that intercepts `vkSetDebugUtilsObjectNameEXT`.
+        let recording_instance = ash::Instance::load_with(
+            |name| {
+                if name == c"vkGetDeviceProcAddr" {
+                    let get_device_proc_addr: vk::PFN_vkGetDeviceProcAddr =
+                        recording_get_device_proc_addr;
+                    get_device_proc_addr as *const c_void
+                } else {
+                    std::ptr::null()
+                }
+            },
+            ctx.instance.handle(),
+        );
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                debug_utils: Some(ash::ext::debug_utils::Device::new(
+                    &recording_instance,
+                    &ctx.logical_device,
+                )),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        let allocation = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let dedicated = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    is_dedicated: true,
+                    name: Some("shadow map".into()),
+                    ..descriptor
+                },
+            )
+            .unwrap();
+
+        let memory_type_index = match alloc.find_allocation(allocation.device_memory(), 0) {
+            Some(AllocationLookup::Allocation(info)) => info.memory_type_index,
+            lookup => panic!("unexpected lookup {:?}", lookup),
+        };
+        let object_names = OBJECT_NAMES.lock().unwrap().clone();
+        // Memory blocks without an allocation name get a generated one.
+        assert!(object_names.contains(&(
+            allocation.device_memory().as_raw(),
+            format!("ash-alloc Static type{} block1", memory_type_index)
+        )));
+        // Dedicated blocks are named after their allocation.
+        assert!(object_names.contains(&(dedicated.device_memory().as_raw(), "shadow map".into())));
+
+        alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+        alloc.deallocate(&ctx.logical_device, &dedicated).unwrap();
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_debug_margin() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                debug_margin: 16,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::CpuToGpu,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: Some("staging".into()),
+            tag: 0,
+        };
+
+        let mut intact = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let mut corrupted = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        assert_eq!(intact.size(), 1024);
+        assert!(corrupted.offset() >= intact.offset() + intact.size() + 2 * 16);
+
+        // Writes inside the allocation don't touch the margins.
+        intact.mapped_slice_mut().unwrap().unwrap().fill(0xFF);
+        assert!(alloc.check_corruption().unwrap().is_empty());
+
+        // Write one byte past the end of the allocation.
+        let slice = corrupted.mapped_slice_mut().unwrap().unwrap();
+        slice.as_mut_ptr().add(slice.len()).write(0);
+
+        let corruptions = alloc.check_corruption().unwrap();
+        assert_eq!(corruptions.len(), 1);
+        assert_eq!(corruptions[0].offset, corrupted.offset());
+        assert_eq!(corruptions[0].size, corrupted.size());
+        assert_eq!(corruptions[0].name.as_deref(), Some("staging"));
+
+        assert!(matches!(
+            alloc.deallocate(&ctx.logical_device, &corrupted),
+            Err(AllocatorError::MemoryCorruption(_))
+        ));
+        alloc.deallocate(&ctx.logical_device, &intact).unwrap();
+        assert_eq!(alloc.allocation_count(), 0);
+
+        // The leading margin is padded up to the alignment, so this doesn't fit into a block.
+        let aligned = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    requirements: vk::MemoryRequirements::default()
+                        .alignment(512 * 1024)
+                        .size(512 * 1024)
+                        .memory_type_bits(u32::MAX),
+                    ..descriptor.clone()
+                },
+            )
+            .unwrap();
+        match alloc.find_allocation(aligned.device_memory(), 0) {
+            Some(AllocationLookup::Allocation(info)) => assert!(info.is_dedicated),
+            lookup => panic!("unexpected lookup {:?}", lookup),
+        }
+        alloc.deallocate(&ctx.logical_device, &aligned).unwrap();
+
+        // Only host visible pools have margins, so only they need a dedicated block.
+        let large = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    location: MemoryLocation::GpuOnly,
+                    requirements: vk::MemoryRequirements::default()
+                        .alignment(256)
+                        .size((1 << 20) - 16)
+                        .memory_type_bits(u32::MAX),
+                    ..descriptor
+                },
+            )
+            .unwrap();
+        match alloc.find_allocation(large.device_memory(), 0) {
+            Some(AllocationLookup::Allocation(info)) => {
+                assert_eq!(info.is_dedicated, info.is_mapped)
+            }
+            lookup => panic!("unexpected lookup {:?}", lookup),
+        }
+        alloc.deallocate(&ctx.logical_device, &large).unwrap();
+
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_debug_fill() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let patterns = DebugFillPatterns::default();
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                debug_fill: Some(patterns),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::CpuToGpu,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        let mut allocation = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let slice = allocation.mapped_slice_mut().unwrap().unwrap();
+        assert!(slice.iter().all(|byte| *byte == patterns.allocate));
+        slice.fill(0);
+
+        alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+        // The block stays mapped, so the freed memory can still be inspected.
+        let slice = allocation.mapped_slice().unwrap().unwrap();
+        assert!(slice.iter().all(|byte| *byte == patterns.free));
+
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_validate() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                debug_margin: 16,
+                validate_operations: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let mut allocations = Vec::new();
+        let rng = Rng::from_seed_with_64bit(7);
+
+        for _ in 0..2_000 {
+            if allocations.is_empty() || rng.f32() <= 0.6 {
+                let size = (rng.mod_usize(4096) + 1) as u64;
+                let allocation = alloc
+                    .allocate(
+                        &ctx.logical_device,
+                        &AllocationDescriptor {
+                            location: if rng.bool() {
+                                MemoryLocation::CpuToGpu
+                            } else {
+                                MemoryLocation::GpuOnly
+                            },
+                            requirements: vk::MemoryRequirements::default()
+                                .alignment(1 << rng.mod_usize(10))
+                                .size(size)
+                                .memory_type_bits(u32::MAX),
+                            lifetime: TestLifetime::Static,
+                            is_dedicated: false,
+                            is_optimal: rng.bool(),
+                            device_mask: 0,
+                            memory_hook: None,
+                            name: None,
+                            tag: 0,
+                        },
+                    )
+                    .unwrap();
+                allocations.push(allocation);
+            } else {
+                let allocation = allocations.remove(rng.mod_usize(allocations.len()));
+                alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+            }
+
+            alloc.validate().unwrap();
+        }
+
+        for allocation in allocations.drain(..) {
+            alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+        }
+        alloc.validate().unwrap();
+        assert_eq!(alloc.allocation_count(), 0);
+
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_statistics() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        let a0 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let a1 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let dedicated = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    is_dedicated: true,
+                    ..descriptor
+                },
+            )
+            .unwrap();
+
+        let statistics = alloc.statistics().unwrap();
+        let total = statistics.total;
+        assert_eq!(total.allocation_count, 3);
+        assert_eq!(total.allocation_bytes, 3 * 1024);
+        assert_eq!(total.dedicated_allocation_count, 1);
+        assert_eq!(total.dedicated_allocation_bytes, 1024);
+        assert_eq!(total.block_count, 2);
+        assert_eq!(total.block_bytes, (1 << 20) + 1024);
+        assert_eq!(total.free_bytes, total.block_bytes - total.allocation_bytes);
+        assert_eq!(total.largest_free_range, (1 << 20) - 2 * 1024);
+        assert_eq!(total.allocation_bytes, alloc.used_bytes());
+
+        assert_eq!(statistics.lifetimes.len(), 1);
+        assert_eq!(statistics.lifetimes[&TestLifetime::Static], total);
+        assert_eq!(
+            statistics
+                .memory_types
+                .iter()
+                .map(|statistics| statistics.allocation_count)
+                .sum::<usize>(),
+            3
+        );
+        assert_eq!(
+            statistics
+                .heaps
+                .iter()
+                .map(|statistics| statistics.block_bytes)
+                .sum::<u64>(),
+            total.block_bytes
+        );
+
+        alloc.deallocate(&ctx.logical_device, &a0).unwrap();
+        alloc.deallocate(&ctx.logical_device, &a1).unwrap();
+        alloc.deallocate(&ctx.logical_device, &dedicated).unwrap();
+
+        let total = alloc.statistics().unwrap().total;
+        assert_eq!(total.allocation_count, 0);
+        assert_eq!(total.block_count, 1);
+        assert_eq!(total.largest_free_range, 1 << 20);
+
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_statistics_debug_margin() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                debug_margin: 16,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::CpuToGpu,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        // The allocation starts after the leading margin padded to the alignment and is followed
+        // by the trailing margin. Only the margins themselves are not free.
+        let a0 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let total = alloc.statistics().unwrap().total;
+        assert_eq!(total.block_bytes, 1 << 20);
+        assert_eq!(total.allocation_bytes, 1024);
+        assert_eq!(total.free_bytes, (1 << 20) - 1024 - 2 * 16);
+        assert_eq!(
+            total.largest_free_range,
+            (1 << 20) - (256 + 1024 + 16) - 2 * 16
+        );
+
+        // The fragmentation agrees on the largest free range. Its free bytes don't include the
+        // alignment padding in front of the allocation.
+        let fragmentation = alloc.fragmentation().total;
+        assert_eq!(fragmentation.largest_free_range, total.largest_free_range);
+        assert_eq!(fragmentation.free_bytes, total.largest_free_range);
+        assert_eq!(
+            fragmentation.free_bytes,
+            total.free_bytes - (256 - 16) - 2 * 16
+        );
+
+        // Only the alignment padding in front of the leading margin is unused.
+        assert_eq!(alloc.unused_bytes(), 256 - 16);
+        assert_eq!(alloc.unused_range_count(), 1);
+
+        // The largest free range can be allocated without a new block.
+        let largest = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    requirements: vk::MemoryRequirements::default()
+                        .alignment(16)
+                        .size(total.largest_free_range)
+                        .memory_type_bits(u32::MAX),
+                    ..descriptor.clone()
+                },
+            )
+            .unwrap();
+        assert_eq!(largest.device_memory(), a0.device_memory());
+        assert_eq!(alloc.statistics().unwrap().total.block_count, 1);
+
+        alloc.deallocate(&ctx.logical_device, &largest).unwrap();
+        alloc.deallocate(&ctx.logical_device, &a0).unwrap();
+
+        let total = alloc.statistics().unwrap().total;
+        assert_eq!(total.free_bytes, 1 << 20);
+        assert_eq!(total.largest_free_range, (1 << 20) - 2 * 16);
+        let fragmentation = alloc.fragmentation().total;
+        assert_eq!(fragmentation.free_bytes, total.largest_free_range);
+        assert_eq!(fragmentation.largest_free_range, total.largest_free_range);
+
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_usage_counters() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(4096)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        let a0 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let a1 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        assert_eq!(alloc.allocation_count(), 2);
+        assert_eq!(alloc.used_bytes(), 2 * 4096);
+        assert_eq!(alloc.block_count(), 1);
+        assert_eq!(alloc.block_bytes(), 1 << 20);
+
+        alloc.deallocate(&ctx.logical_device, &a0).unwrap();
+        assert_eq!(alloc.used_bytes(), 4096);
+        assert_eq!(alloc.peak_used_bytes(), 2 * 4096);
+        assert_eq!(alloc.peak_block_bytes(), 1 << 20);
+
+        alloc.reset_peaks();
+        assert_eq!(alloc.peak_used_bytes(), 4096);
+
+        alloc.deallocate(&ctx.logical_device, &a1).unwrap();
+        assert_eq!(alloc.allocation_count(), 0);
+        assert_eq!(alloc.used_bytes(), 0);
+
+        // Only live blocks are counted. The slot of a freed dedicated block is not.
+        let dedicated = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    is_dedicated: true,
+                    ..descriptor
+                },
+            )
+            .unwrap();
+        assert_eq!(alloc.block_count(), 2);
+        assert_eq!(alloc.block_bytes(), (1 << 20) + 4096);
+        alloc.deallocate(&ctx.logical_device, &dedicated).unwrap();
+        assert_eq!(alloc.block_count(), 1);
+        assert_eq!(alloc.block_bytes(), 1 << 20);
+
+        alloc.cleanup(&ctx.logical_device);
+        assert_eq!(alloc.block_count(), 0);
+        assert_eq!(alloc.block_bytes(), 0);
+        assert_eq!(alloc.peak_block_bytes(), 1 << 20);
+    }
+}
+
+#[test]
+fn allocator_dump_json() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: true,
+            device_mask: 0,
+            memory_hook: None,
+            name: Some("texture \"a\"".into()),
+            tag: 0,
+        };
+
+        let allocation = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+
+        let json = alloc.dump_json().unwrap();
+        assert!(json.starts_with("{\"General\":{\"API\":\"Vulkan\""));
+        assert!(json.contains("\"CustomPools\":{\"Type "));
+        assert!(json.contains("\"Name\":\"Static\",\"PreferredBlockSize\":1048576"));
+        assert!(json.contains(
+            "{\"Offset\":0,\"Type\":\"IMAGE_OPTIMAL\",\"Size\":1024,\"Name\":\"texture \\\"a\\\"\""
+        ));
+        assert!(json.contains("{\"Offset\":1024,\"Type\":\"FREE\",\"Size\":1047552}"));
+        #[cfg(feature = "backtrace")]
+        assert!(json.contains(",\"Backtrace\":\""));
+        assert!(json.ends_with('}'));
+
+        alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_dump_json_debug_margin() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                debug_margin: 16,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let allocation = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    location: MemoryLocation::CpuToGpu,
+                    requirements: vk::MemoryRequirements::default()
+                        .alignment(256)
+                        .size(1024)
+                        .memory_type_bits(u32::MAX),
+                    lifetime: TestLifetime::Static,
+                    is_dedicated: false,
+                    is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0xAB,
+                },
+            )
+            .unwrap();
+        assert_eq!(allocation.offset(), 256);
+
+        // The leading margin with its padding and the trailing margin show up as free ranges.
+        let json = alloc.dump_json().unwrap();
+        assert!(json.contains("\"UnusedBytes\":1047552,\"Allocations\":1,\"UnusedRanges\":2"));
+        assert!(json.contains("{\"Offset\":0,\"Type\":\"FREE\",\"Size\":256}"));
+        assert!(json
+            .contains("{\"Offset\":256,\"Type\":\"BUFFER\",\"Size\":1024,\"CustomData\":\"0xab\""));
+        assert!(json.contains("{\"Offset\":1280,\"Type\":\"FREE\",\"Size\":1047296}"));
+
+        alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_fragmentation() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        let a0 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let a1 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let a2 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+
+        let fragmentation = alloc.fragmentation();
+        assert_eq!(fragmentation.pools.len(), 1);
+        assert_eq!(fragmentation.total.free_bytes, (1 << 20) - 3 * 1024);
+        assert_eq!(fragmentation.total.largest_free_range, (1 << 20) - 3 * 1024);
+        assert_eq!(fragmentation.total.score(), 0.0);
+
+        alloc.deallocate(&ctx.logical_device, &a1).unwrap();
+
+        let fragmentation = alloc.fragmentation();
+        assert_eq!(fragmentation.pools[0].fragmentation, fragmentation.total);
+        assert_eq!(fragmentation.total.free_bytes, (1 << 20) - 2 * 1024);
+        assert_eq!(fragmentation.total.largest_free_range, (1 << 20) - 3 * 1024);
+        assert_eq!(fragmentation.total.free_chunks_per_bucket[1], 1);
+        assert_eq!(
+            fragmentation
+                .total
+                .free_chunks_per_bucket
+                .iter()
+                .sum::<usize>(),
+            2
+        );
+        assert!(fragmentation.total.score() > 0.0);
+        assert!(fragmentation.total.score() < 0.01);
+
+        alloc.deallocate(&ctx.logical_device, &a0).unwrap();
+        alloc.deallocate(&ctx.logical_device, &a2).unwrap();
+
+        let fragmentation = alloc.fragmentation();
+        assert_eq!(fragmentation.total.free_bytes, 1 << 20);
+        assert_eq!(fragmentation.total.score(), 0.0);
+
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_request_histograms() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        let a0 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let a1 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let a2 = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    requirements: vk::MemoryRequirements::default()
+                        .alignment(4096)
+                        .size(4096)
+                        .memory_type_bits(u32::MAX),
+                    ..descriptor
+                },
+            )
+            .unwrap();
+
+        let statistics = alloc.statistics().unwrap();
+        assert_eq!(statistics.requests.len(), 1);
+        let histogram = statistics.requests.values().next().unwrap();
+        assert_eq!(histogram.sizes, vec![0, 2, 0, 1]);
+        assert_eq!(histogram.alignments, vec![2, 0, 0, 1]);
+
+        alloc.reset_request_histograms();
+        assert!(alloc.statistics().unwrap().requests.is_empty());
+
+        alloc.deallocate(&ctx.logical_device, &a0).unwrap();
+        alloc.deallocate(&ctx.logical_device, &a1).unwrap();
+        alloc.deallocate(&ctx.logical_device, &a2).unwrap();
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_snapshot_diff() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+
+        let a0 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let before = alloc.snapshot();
+        assert_eq!(before.allocations.len(), 1);
+
+        let a1 = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    name: Some("level".into()),
+                    ..descriptor.clone()
+                },
+            )
+            .unwrap();
+        let a2 = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    is_dedicated: true,
+                    ..descriptor
+                },
+            )
+            .unwrap();
+        assert_ne!(a0.id(), a1.id());
+        assert_ne!(a1.id(), a2.id());
+
+        let loaded = alloc.snapshot();
+        let diff = AllocationSnapshot::diff(&before, &loaded);
+        assert!(diff.freed.is_empty());
+        assert_eq!(diff.created.len(), 2);
+        assert_eq!(diff.created[0].id, a1.id());
+        assert_eq!(diff.created[0].name.as_deref(), Some("level"));
+        assert_eq!(diff.created[1].id, a2.id());
+        assert!(diff.created[1].is_dedicated);
+
+        alloc.deallocate(&ctx.logical_device, &a1).unwrap();
+        alloc.deallocate(&ctx.logical_device, &a2).unwrap();
+
+        let unloaded = alloc.snapshot();
+        assert!(AllocationSnapshot::diff(&before, &unloaded).is_empty());
+        let diff = AllocationSnapshot::diff(&loaded, &unloaded);
+        assert!(diff.created.is_empty());
+        assert_eq!(diff.freed.len(), 2);
+
+        alloc.deallocate(&ctx.logical_device, &a0).unwrap();
+        assert!(!alloc.snapshot().contains_lifetime(TestLifetime::Static));
+
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_for_each_allocation() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::CpuToGpu,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: Some("uniforms".into()),
+            tag: 0,
+        };
+
+        let a0 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let a1 = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    is_dedicated: true,
+                    name: None,
+                    ..descriptor
+                },
+            )
+            .unwrap();
+
+        let mut visited = Vec::new();
+        alloc.for_each_allocation(|info| visited.push(info.clone()));
+        visited.sort_by_key(|info| info.id);
+
+        assert_eq!(visited.len(), 2);
+        assert_eq!(visited[0].id, a0.id());
+        assert_eq!(visited[0].name.as_deref(), Some("uniforms"));
+        assert_eq!(visited[0].device_memory, a0.device_memory());
+        assert_eq!(visited[0].offset, a0.offset());
+        assert_eq!(visited[0].size, 1024);
+        assert!(!visited[0].is_dedicated);
+        assert!(visited[0].is_mapped);
+        assert_eq!(visited[1].id, a1.id());
+        assert!(visited[1].is_dedicated);
+        assert!(visited[1].is_mapped);
+
+        alloc.deallocate(&ctx.logical_device, &a0).unwrap();
+        alloc.deallocate(&ctx.logical_device, &a1).unwrap();
+
+        let mut count = 0;
+        alloc.for_each_allocation(|_| count += 1);
+        assert_eq!(count, 0);
+
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_find_allocation() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1000)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: Some("vertices".into()),
+            tag: 0,
+        };
+
+        let a0 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let a1 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let dedicated = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    is_dedicated: true,
+                    ..descriptor
+                },
+            )
+            .unwrap();
+        assert_eq!(a0.device_memory(), a1.device_memory());
+        assert_eq!(a1.offset(), 1024);
+
+        let device_memory = a0.device_memory();
+        match alloc.find_allocation(device_memory, 10) {
+            Some(AllocationLookup::Allocation(info)) => {
+                assert_eq!(info.id, a0.id());
+                assert_eq!(info.name.as_deref(), Some("vertices"));
+            }
+            lookup => panic!("unexpected lookup {:?}", lookup),
+        }
+        assert!(matches!(
+            alloc.find_allocation(device_memory, 1010),
+            Some(AllocationLookup::Padding)
+        ));
+        match alloc.find_allocation(device_memory, 1024) {
+            Some(AllocationLookup::Allocation(info)) => assert_eq!(info.id, a1.id()),
+            lookup => panic!("unexpected lookup {:?}", lookup),
+        }
+        match alloc.find_allocation(device_memory, 4096) {
+            Some(AllocationLookup::Free { offset, size }) => {
+                assert_eq!(offset, 2024);
+                assert_eq!(size, (1 << 20) - 2024);
+            }
+            lookup => panic!("unexpected lookup {:?}", lookup),
+        }
+        match alloc.find_allocation(dedicated.device_memory(), 500) {
+            Some(AllocationLookup::Allocation(info)) => assert_eq!(info.id, dedicated.id()),
+            lookup => panic!("unexpected lookup {:?}", lookup),
+        }
+        assert!(alloc.find_allocation(device_memory, 1 << 20).is_none());
+        assert!(alloc.find_allocation(vk::DeviceMemory::null(), 0).is_none());
+
+        alloc.deallocate(&ctx.logical_device, &a0).unwrap();
+        alloc.deallocate(&ctx.logical_device, &a1).unwrap();
+        let dedicated_memory = dedicated.device_memory();
+        alloc.deallocate(&ctx.logical_device, &dedicated).unwrap();
+        assert!(alloc.find_allocation(dedicated_memory, 0).is_none());
+
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_device_fault_attribution() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: Some("lights".into()),
+            tag: 0,
+        };
+
+        let a0 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let a1 = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    name: Some("particles".into()),
+                    ..descriptor.clone()
+                },
+            )
+            .unwrap();
+        alloc.register_device_address(&a0, 0x10000).unwrap();
+        alloc.register_device_address(&a1, 0x20000).unwrap();
+
+        let faults = alloc.attribute_device_faults(&[
+            vk::DeviceFaultAddressInfoEXT::default()
+                .address_type(vk::DeviceFaultAddressTypeEXT::READ_INVALID)
+                .reported_address(0x10010),
+            vk::DeviceFaultAddressInfoEXT::default()
+                .address_type(vk::DeviceFaultAddressTypeEXT::WRITE_INVALID)
+                .reported_address(0x18123)
+                .address_precision(4096),
+            vk::DeviceFaultAddressInfoEXT::default()
+                .address_type(vk::DeviceFaultAddressTypeEXT::INSTRUCTION_POINTER_FAULT)
+                .reported_address(0x10010),
+        ]);
+        assert_eq!(faults.len(), 3);
+
+        assert_eq!(faults[0].overlapping.len(), 1);
+        assert_eq!(faults[0].overlapping[0].name.as_deref(), Some("lights"));
+        assert_eq!(faults[0].overlapping[0].lifetime, TestLifetime::Static);
+        assert!(faults[0].preceding.is_none());
+        assert_eq!(faults[0].following.as_ref().unwrap().id, a1.id());
+
+        assert!(faults[1].overlapping.is_empty());
+        assert_eq!(faults[1].preceding.as_ref().unwrap().id, a0.id());
+        assert_eq!(
+            faults[1].following.as_ref().unwrap().name.as_deref(),
+            Some("particles")
+        );
+
+        // Instruction pointers are reported without attribution.
+        assert_eq!(faults[2].address_info.reported_address, 0x10010);
+        assert!(faults[2].overlapping.is_empty());
+        assert!(faults[2].preceding.is_none());
+        assert!(faults[2].following.is_none());
+
+        // An aliasing allocation that starts before a0 but ends right at the fault is closer.
+        let a2 = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    requirements: vk::MemoryRequirements::default()
+                        .alignment(256)
+                        .size(0x9000)
+                        .memory_type_bits(u32::MAX),
+                    name: Some("terrain".into()),
+                    ..descriptor.clone()
+                },
+            )
+            .unwrap();
+        alloc.register_device_address(&a2, 0xF000).unwrap();
+        let faults = alloc.attribute_device_faults(&[vk::DeviceFaultAddressInfoEXT::default()
+            .address_type(vk::DeviceFaultAddressTypeEXT::WRITE_INVALID)
+            .reported_address(0x18123)
+            .address_precision(4096)]);
+        assert_eq!(faults[0].preceding.as_ref().unwrap().id, a2.id());
+        alloc.deallocate(&ctx.logical_device, &a2).unwrap();
+
+        alloc.deallocate(&ctx.logical_device, &a0).unwrap();
+        assert!(matches!(
+            alloc.register_device_address(&a0, 0x10000),
+            Err(AllocatorError::StaleAllocation)
+        ));
+        let faults = alloc.attribute_device_faults(&[
+            vk::DeviceFaultAddressInfoEXT::default().reported_address(0x10010)
+        ]);
+        assert!(faults[0].overlapping.is_empty());
+        assert!(faults[0].preceding.is_none());
+
+        alloc.deallocate(&ctx.logical_device, &a1).unwrap();
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_import_host_pointer() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        if !ctx.external_memory_host {
+            return;
+        }
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let external_memory_host =
+            ash::ext::external_memory_host::Device::new(&ctx.instance, &ctx.logical_device);
+        let handle_type = vk::ExternalMemoryHandleTypeFlags::HOST_ALLOCATION_EXT;
+
+        let mut host_properties = vk::PhysicalDeviceExternalMemoryHostPropertiesEXT::default();
+        let mut properties =
+            vk::PhysicalDeviceProperties2::default().push_next(&mut host_properties);
+        ctx.instance
+            .get_physical_device_properties2(ctx.physical_device, &mut properties);
+        let alignment = host_properties.min_imported_host_pointer_alignment;
+
+        let layout =
+            std::alloc::Layout::from_size_align(alignment as usize, alignment as usize).unwrap();
+        let host_pointer = std::alloc::alloc_zeroed(layout).cast::<c_void>();
+        assert!(!host_pointer.is_null());
+
+        let mut pointer_properties = vk::MemoryHostPointerPropertiesEXT::default();
+        (external_memory_host
+            .fp()
+            .get_memory_host_pointer_properties_ext)(
+            ctx.logical_device.handle(),
+            handle_type,
+            host_pointer,
+            &mut pointer_properties,
+        )
+        .result()
+        .unwrap();
+
+        let descriptor = ImportDescriptor {
+            location: MemoryLocation::CpuToGpu,
+            requirements: vk::MemoryRequirements::default()
+                .size(alignment)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            device_mask: 0,
+            name: Some("host memory".into()),
+            tag: 7,
+        };
+
+        assert_eq!(
+            alloc
+                .import_host_pointer(
+                    &ctx.logical_device,
+                    &external_memory_host,
+                    host_pointer,
+                    handle_type,
+                    &ImportDescriptor {
+                        requirements: descriptor.requirements.size(0),
+                        ..descriptor.clone()
+                    },
+                )
+                .unwrap_err(),
+            AllocatorError::ZeroSize
+        );
+        assert_eq!(
+            alloc
+                .import_host_pointer(
+                    &ctx.logical_device,
+                    &external_memory_host,
+                    host_pointer,
+                    handle_type,
+                    &ImportDescriptor {
+                        requirements: descriptor
+                            .requirements
+                            .memory_type_bits(!pointer_properties.memory_type_bits),
+                        ..descriptor.clone()
+                    },
+                )
+                .unwrap_err(),
+            AllocatorError::NoCompatibleMemoryTypeFound
+        );
+
+        let allocation = alloc
+            .import_host_pointer(
+                &ctx.logical_device,
+                &external_memory_host,
+                host_pointer,
+                handle_type,
+                &descriptor,
+            )
+            .unwrap();
+        assert_eq!(allocation.size(), alignment);
+        assert_eq!(allocation.name(), Some("host memory"));
+        assert_eq!(allocation.tag(), 7);
+        match alloc.find_allocation(allocation.device_memory(), 0) {
+            Some(AllocationLookup::Allocation(info)) => {
+                assert_ne!(
+                    pointer_properties.memory_type_bits & (1 << info.memory_type_index),
+                    0
+                );
+            }
+            lookup => panic!("unexpected lookup {:?}", lookup),
+        }
+        assert_eq!(alloc.allocation_count(), 1);
+
+        alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+        assert_eq!(alloc.allocation_count(), 0);
+        assert_eq!(alloc.block_count(), 0);
+
+        alloc.cleanup(&ctx.logical_device);
+        std::alloc::dealloc(host_pointer.cast(), layout);
+    }
+}
+
+#[test]
+fn allocator_import_fd() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        if !ctx.external_memory_fd {
+            return;
+        }
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let external_memory_fd =
+            ash::khr::external_memory_fd::Device::new(&ctx.instance, &ctx.logical_device);
+        let handle_type = vk::ExternalMemoryHandleTypeFlags::OPAQUE_FD;
+        let size = 1 << 16;
+
+        // Use the memory type the allocator picks for the location to export the memory.
+        let probe = alloc
+            .allocate(
+                &ctx.logical_device,
+                &AllocationDescriptor {
+                    location: MemoryLocation::GpuOnly,
+                    requirements: vk::MemoryRequirements::default()
+                        .alignment(256)
+                        .size(size)
+                        .memory_type_bits(u32::MAX),
+                    lifetime: TestLifetime::Static,
+                    is_dedicated: true,
+                    is_optimal: false,
+                    device_mask: 0,
+                    memory_hook: None,
+                    name: None,
+                    tag: 0,
+                },
+            )
+            .unwrap();
+        let memory_type_index = match alloc.find_allocation(probe.device_memory(), 0) {
+            Some(AllocationLookup::Allocation(info)) => info.memory_type_index,
+            lookup => panic!("unexpected lookup {:?}", lookup),
+        };
+        alloc.deallocate(&ctx.logical_device, &probe).unwrap();
+
+        let mut export_info = vk::ExportMemoryAllocateInfo::default().handle_types(handle_type);
+        let exported = ctx
+            .logical_device
+            .allocate_memory(
+                &vk::MemoryAllocateInfo::default()
+                    .allocation_size(size)
+                    .memory_type_index(memory_type_index)
+                    .push_next(&mut export_info),
+                None,
+            )
+            .unwrap();
+        let fd = external_memory_fd
+            .get_memory_fd(
+                &vk::MemoryGetFdInfoKHR::default()
+                    .memory(exported)
+                    .handle_type(handle_type),
+            )
+            .unwrap();
+
+        // The driver can't report the memory types of opaque handles, so the descriptor names the
+        // exported memory type.
+        let descriptor = ImportDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .size(size)
+                .memory_type_bits(1 << memory_type_index),
+            lifetime: TestLifetime::Static,
+            device_mask: 0,
+            name: Some("exported".into()),
+            tag: 3,
+        };
+
+        // A failed import keeps the ownership of the file descriptor with the caller.
+        assert_eq!(
+            alloc
+                .import_fd(
+                    &ctx.logical_device,
+                    &external_memory_fd,
+                    fd,
+                    handle_type,
+                    &ImportDescriptor {
+                        requirements: descriptor.requirements.size(0),
+                        ..descriptor.clone()
+                    },
+                )
+                .unwrap_err(),
+            AllocatorError::ZeroSize
+        );
+
+        let allocation = alloc
+            .import_fd(
+                &ctx.logical_device,
+                &external_memory_fd,
+                fd,
+                handle_type,
+                &descriptor,
+            )
+            .unwrap();
+        assert_eq!(allocation.size(), size);
+        assert_eq!(allocation.name(), Some("exported"));
+        match alloc.find_allocation(allocation.device_memory(), 0) {
+            Some(AllocationLookup::Allocation(info)) => {
+                assert_eq!(info.memory_type_index, memory_type_index);
+                assert_eq!(info.tag, 3);
+            }
+            lookup => panic!("unexpected lookup {:?}", lookup),
+        }
+
+        alloc.deallocate(&ctx.logical_device, &allocation).unwrap();
+        assert_eq!(alloc.allocation_count(), 0);
+        assert_eq!(alloc.block_count(), 0);
+
+        ctx.logical_device.free_memory(exported, None);
+        alloc.cleanup(&ctx.logical_device);
+    }
+}
+
+#[test]
+fn allocator_device_mask() {
+    unsafe {
+        let ctx = fixture::VulkanContext::new(vk::make_api_version(0, 1, 2, 0));
+        let alloc = Allocator::new(
+            &ctx.instance,
+            ctx.physical_device,
+            &AllocatorDescriptor {
+                block_size: 20, // 1 MiB
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let descriptor = AllocationDescriptor {
+            location: MemoryLocation::GpuOnly,
+            requirements: vk::MemoryRequirements::default()
+                .alignment(256)
+                .size(1024)
+                .memory_type_bits(u32::MAX),
+            lifetime: TestLifetime::Static,
+            is_dedicated: false,
+            is_optimal: false,
+            device_mask: 0,
+            memory_hook: None,
+            name: None,
+            tag: 0,
+        };
+        // The device created by the fixture is a device group with a single physical device.
+        let masked_descriptor = AllocationDescriptor {
+            device_mask: 1,
+            ..descriptor.clone()
+        };
+
+        let a0 = alloc.allocate(&ctx.logical_device, &descriptor).unwrap();
+        let a1 = alloc
+            .allocate(&ctx.logical_device, &masked_descriptor)
+            .unwrap();
+        let a2 = alloc
+            .allocate(&ctx.logical_device, &masked_descriptor)
+            .unwrap();
+        assert_eq!(a0.device_mask(), 0);
+        assert_eq!(a1.device_mask(), 1);
+        assert_ne!(a0.device_memory(), a1.device_memory());
+        assert_eq!(a1.device_memory(), a2.device_memory());
+        assert_eq!(alloc.block_count(), 2);
+
+        let buffer = ctx
+            .logical_device
+            .create_buffer(
+                &vk::BufferCreateInfo::default()
+                    .size(1024)
+                    .usage(vk::BufferUsageFlags::TRANSFER_DST)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE),
+                None,
+            )
+            .unwrap();
+        let buffer_allocation = alloc
+            .allocate_memory_for_buffer_with_device_mask(
+                &ctx.logical_device,
+                buffer,
+                MemoryLocation::GpuOnly,
+                TestLifetime::Static,
+                1,
+            )
+            .unwrap();
+        assert_eq!(buffer_allocation.device_mask(), 1);
+        let device_indices = [0];
+        let mut buffer_group_info =
+            vk::BindBufferMemoryDeviceGroupInfo::default().device_indices(&device_indices);
+        alloc
+            .bind_buffer_memory(
+                &ctx.logical_device,
+                buffer,
+                &buffer_allocation,
+                Some(&mut buffer_group_info),
+            )
+            .unwrap();
+
+        let image = ctx
+            .logical_device
+            .create_image(
+                &vk::ImageCreateInfo::default()
+                    .image_type(vk::ImageType::TYPE_2D)
+                    .format(vk::Format::R8G8B8A8_UNORM)
+                    .extent(vk::Extent3D {
+                        width: 64,
+                        height: 64,
+                        depth: 1,
+                    })
+                    .mip_levels(1)
+                    .array_layers(1)
+                    .samples(vk::SampleCountFlags::TYPE_1)
+                    .tiling(vk::ImageTiling::OPTIMAL)
+                    .usage(vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                    .initial_layout(vk::ImageLayout::UNDEFINED),
+                None,
+            )
+            .unwrap();
+        let image_allocation = alloc
+            .allocate_memory_for_image_with_device_mask(
+                &ctx.logical_device,
+                image,
+                MemoryLocation::GpuOnly,
+                TestLifetime::Static,
+                true,
+                1,
+            )
+            .unwrap();
+        assert_eq!(image_allocation.device_mask(), 1);
+        let mut image_group_info =
+            vk::BindImageMemoryDeviceGroupInfo::default().device_indices(&device_indices);
+        alloc
+            .bind_image_memory(
+                &ctx.logical_device,
+                image,
+                &image_allocation,
+                Some(&mut image_group_info),
+            )
+            .unwrap();
+
+        ctx.logical_device.destroy_buffer(buffer, None);
+        ctx.logical_device.destroy_image(image, None);
+        alloc
+            .deallocate(&ctx.logical_device, &buffer_allocation)
+            .unwrap();
+        alloc
+            .deallocate(&ctx.logical_device, &image_allocation)
+            .unwrap();
+        alloc.deallocate(&ctx.logical_device, &a0).unwrap();
+        alloc.deallocate(&ctx.logical_device, &a1).unwrap();
+        alloc.deallocate(&ctx.logical_device, &a2).unwrap();
+        alloc.cleanup(&ctx.logical_device);
+    }
+}